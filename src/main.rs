@@ -1,324 +1,10084 @@
-use console::{Key, Term};
-use rand::Rng;
+use console::{measure_text_width, pad_str, style, Alignment, Key, Term};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
 use std::char;
-use std::fmt::Debug;
-use tokio::time::{sleep, Duration};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt::{self, Debug};
+use std::fs;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Duration, Instant};
 
 const GRASS: char = '🟩';
 const TREE: char = '🌲';
 const ROAD: char = '⬛';
 const CAR: char = '🚗';
+const TAXI: char = '🚕';
+const SUV: char = '🚙';
+const BUS: char = '🚌';
+const VEHICLES: [char; 4] = [CAR, TAXI, SUV, BUS];
 const WATER: char = '🟦';
 const PAD: char = '🟢';
+const ICE: char = '🧊';
+const FROG: char = '🐸';
+const HURT_FROG: char = '🤕';
+const RED_LIGHT: char = '🔴';
+const GREEN_LIGHT: char = '💚';
+const COIN: char = '💰';
+const POWER_UP: char = '⭐';
+const TRAIN: char = '🚂';
+const NPC: char = '🐊';
+const ROCK: char = '🪨';
 
-#[derive(Debug)]
-pub struct KeyReader {
-    jh: Option<tokio::task::JoinHandle<Key>>,
+const CHECKPOINT_SLOTS: usize = 3;
+const UNDO_DEPTH: usize = 10;
+const COLLISION_FLASH_FRAMES: u8 = 6;
+const MAX_ROW_RETRIES: usize = 20;
+// Times a new top row is rerolled when it leaves the frog no way up
+const MAX_PASSABILITY_RETRIES: usize = 5;
+// Hard rows allowed in a row before a grass row is forced in
+const MAX_CONSECUTIVE_HARD_ROWS: u8 = 3;
+const TICK_INTERVAL: Duration = Duration::from_millis(50);
+// Moving forward past this row scrolls the board instead of moving the frog up the screen
+const SCROLL_ROW: usize = 3;
+const TRACE_MAX_BYTES: u64 = 1024 * 1024;
+// Background colours (256-colour palette) cycled through by vehicle id
+const VEHICLE_COLORS: [u8; 8] = [196, 208, 226, 46, 51, 27, 201, 93];
+// With fade_in on, an object about to arrive is hinted at with the first glyph and spends its
+// first FADE_FRAMES frames on the board drawn with the later ones
+const FADE_GLYPHS: [char; 3] = ['░', '▒', '▓'];
+const FADE_FRAMES: usize = 2;
+const COIN_CHANCE: f64 = 0.03;
+// Keys the reader thread may get ahead of the game by before it waits
+const KEY_BUFFER: usize = 64;
+// Chance of each cell starting with a tree; roads take theirs from the BoardConfig
+const GRASS_DENSITY: f64 = 0.2;
+// Streams get their pads as pairs lashed together into rafts, roads get cars and trucks
+const STREAM_GROUP_SIZE: usize = 2;
+const STREAM_GROUPS: usize = 2;
+const VEHICLE_LENGTH: RangeInclusive<usize> = 1..=3;
+// How long a two-step river may keep the frog waiting before it counts as uncrossable
+const TWO_STEP_RIVER_TICKS: u64 = 200;
+// Seeds that once broke the game, replayed before every fuzz run
+const FUZZ_REGRESSIONS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fuzz/regressions.txt");
+// Scores at which the game moves up a difficulty level
+const DIFFICULTY_THRESHOLDS: [u32; 5] = [0, 20, 50, 100, 200];
+const COIN_VALUE: u32 = 5;
+const POWER_UP_CHANCE: f64 = 0.1;
+// Above this average difficulty_rating the next grass row always gets a power-up
+const POWER_UP_GUARANTEE_DIFFICULTY: f32 = 6.0;
+// Chance that a new stream is a river with rocks instead, and of each cell holding a rock
+const RIVER_CHANCE: f64 = 0.15;
+const ROCK_CHANCE: f64 = 0.1;
+// Chance that a new row comes with an NPC on it, and what catching one is worth
+const NPC_CHANCE: f64 = 0.05;
+const NPC_BONUS: u32 = 5;
+const SPEED_BOOST_TIME: Duration = Duration::from_secs(5);
+const TRAIN_MIN_SCORE: u32 = 20;
+const TRAIN_CHANCE: f64 = 0.25;
+const TRAIN_ACTIVE_TICKS: u8 = 2;
+// Ticks a frog in open water is swept along by the current before it drowns
+const WATER_DRIFT_GRACE: u8 = 2;
+// Wind: a new direction (left, calm or right) is rolled this often, and while it blows the
+// frog on a water row gets a gust every WIND_GUST_TICKS
+const WIND_CHANGE_TICKS: u64 = 200;
+const WIND_GUST_TICKS: u8 = 30;
+// Hints: how far ahead safe_path searches, how long the path stays drawn and what it costs
+const HINT_MAX_TICKS: usize = 64;
+const HINT_TIME: Duration = Duration::from_secs(3);
+const HINT_COST: u32 = 10;
+// Timed mode: each row crossed puts this much back on the clock
+const TIME_BONUS_PER_ROW: Duration = Duration::from_secs(3);
+const COMBO_TIMEOUT_TICKS: u8 = 10;
+const MAX_COMBO: u32 = 5;
+const MIN_GAP_WIDTH: usize = 1;
+const SPEED_UP_EVERY: u32 = 10;
+// The game also speeds up every this many ticks, hopping or not
+const SPEED_UP_STEPS: u64 = 100;
+// Past SAFE_ROW_MIN_SCORE, every SAFE_ROW_STEPS ticks the next new row is grass
+const SAFE_ROW_STEPS: u64 = 50;
+const SAFE_ROW_MIN_SCORE: u32 = 30;
+const SPEED_UP_STEP: Duration = Duration::from_millis(5);
+const MIN_TICK_INTERVAL: Duration = Duration::from_millis(15);
+const MAX_SPEED: u32 = 8;
+// Long enough for any generated row to shift all the way round and cycle its light
+const CROSSABLE_CHECK_TICKS: usize = 256;
+// What console's Term::clear_screen writes
+const CLEAR_SCREEN: &str = "\r\x1b[2J\r\x1b[H";
+// Bottom-row columns always generated clear of trees; the frog starts somewhere in here
+const START_SPAN: RangeInclusive<usize> = 5..=9;
+const ROW_ORDERING: RowOrdering = RowOrdering::BottomUp;
+
+// gameboard[0] is the bottom row and player.1 counts up from it, while the terminal draws
+// the top line first. Anything drawing onto the board maps row indexes through screen_line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowOrdering {
+    BottomUp,
+    TopDown,
 }
 
-impl KeyReader {
-    pub fn new() -> KeyReader {
-        KeyReader {
-            jh: Some(tokio::spawn(Self::await_key_press())),
+impl RowOrdering {
+    // None when the row isn't on the board
+    pub fn screen_line(self, row_index: usize, height: usize) -> Option<usize> {
+        if row_index >= height {
+            return None;
+        }
+        match self {
+            RowOrdering::BottomUp => Some(height - 1 - row_index),
+            RowOrdering::TopDown => Some(row_index),
         }
     }
+}
 
-    async fn await_key_press() -> Key {
-        let term = Term::stdout();
-        term.read_key().unwrap()
+#[derive(Debug)]
+pub enum ThemeError {
+    MixedWidths {
+        glyph: char,
+        width: usize,
+        expected: usize,
+    },
+    TooWide {
+        glyph: char,
+        width: usize,
+        cell_width: usize,
+    },
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThemeError::MixedWidths {
+                glyph,
+                width,
+                expected,
+            } => write!(
+                f,
+                "glyph {} is {} columns wide but the rest of the theme is {}; set a cell_width",
+                glyph, width, expected
+            ),
+            ThemeError::TooWide {
+                glyph,
+                width,
+                cell_width,
+            } => write!(
+                f,
+                "glyph {} is {} columns wide which does not fit in a cell_width of {}",
+                glyph, width, cell_width
+            ),
+        }
     }
+}
 
-    pub async fn read_key(&mut self) -> Option<Key> {
-        if let Some(handle) = self.jh.take() {
-            match handle.await {
-                Ok(key) => {
-                    self.jh = Some(tokio::spawn(async { Self::await_key_press().await }));
-                    Some(key)
-                }
-                Err(_) => None,
-            }
-        } else {
-            None
+impl std::error::Error for ThemeError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThemePreset {
+    #[default]
+    Day,
+    Night,
+    Desert,
+    Snow,
+}
+
+impl ThemePreset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "day" => Some(ThemePreset::Day),
+            "night" => Some(ThemePreset::Night),
+            "desert" => Some(ThemePreset::Desert),
+            "snow" => Some(ThemePreset::Snow),
+            _ => None,
         }
     }
 }
 
-#[derive(Debug)]
-pub struct BaseRow {
-    objects: Vec<bool>,
-    object_label: char,
-    environment_label: char,
+pub fn terrain_chars(preset: ThemePreset) -> Theme {
+    Theme::preset(preset)
 }
 
-impl BaseRow {
-    pub fn new(objects: Vec<bool>, object_label: char, environment_label: char) -> Self {
+// Glyphs used to draw the board. Rows store the default labels, the theme maps them for display
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub grass: char,
+    pub tree: char,
+    pub road: char,
+    pub car: char,
+    pub water: char,
+    pub pad: char,
+    pub ice: char,
+    pub player: char,
+    // Shown instead of the player when on the last life, if enabled in the config
+    pub player_last_life: char,
+    pub red_light: char,
+    pub green_light: char,
+    pub coin: char,
+    pub power_up: char,
+    pub train: char,
+    pub npc: char,
+    pub rock: char,
+    // 256-colour code the board's frame is drawn in
+    pub border_color: u8,
+    pub cell_width: Option<usize>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
         Self {
-            objects,
-            object_label,
-            environment_label,
+            grass: GRASS,
+            tree: TREE,
+            road: ROAD,
+            car: CAR,
+            water: WATER,
+            pad: PAD,
+            ice: ICE,
+            player: FROG,
+            player_last_life: HURT_FROG,
+            red_light: RED_LIGHT,
+            green_light: GREEN_LIGHT,
+            coin: COIN,
+            power_up: POWER_UP,
+            train: TRAIN,
+            npc: NPC,
+            rock: ROCK,
+            border_color: 34,
+            cell_width: None,
         }
     }
-    pub fn randomized_objects(object_label: char, environment_label: char) -> Self {
-        let mut rng = rand::thread_rng();
-        let mut objects = Vec::with_capacity(14);
-        for _ in 0..14 {
-            objects.push(rng.gen_bool(0.2));
+}
+
+impl Theme {
+    // Plain single-column characters, for logs, tests and terminals without emoji
+    pub fn ascii() -> Self {
+        Self {
+            grass: '.',
+            tree: 'T',
+            road: '_',
+            car: 'C',
+            water: '~',
+            pad: 'O',
+            ice: '=',
+            player: 'F',
+            player_last_life: 'f',
+            red_light: 'R',
+            green_light: 'G',
+            coin: '$',
+            power_up: '*',
+            train: '#',
+            npc: 'N',
+            rock: '@',
+            border_color: 7,
+            cell_width: None,
         }
+    }
+
+    // Glyphs not given here fall back to ones that are: ice is drawn as grass, the last-life
+    // frog as the normal one, lights as trees and pads, pickups as pads, trains and NPCs as
+    // cars and rocks as trees
+    pub fn new(
+        grass: char,
+        tree: char,
+        road: char,
+        car: char,
+        water: char,
+        pad: char,
+        player: char,
+    ) -> Result<Self, ThemeError> {
+        let theme = Self {
+            grass,
+            tree,
+            road,
+            car,
+            water,
+            pad,
+            ice: grass,
+            player,
+            player_last_life: player,
+            red_light: tree,
+            green_light: pad,
+            coin: pad,
+            power_up: pad,
+            train: car,
+            npc: car,
+            rock: tree,
+            border_color: 7,
+            cell_width: None,
+        };
+        theme.validate()?;
+        Ok(theme)
+    }
+
+    // The built-in looks. Everything but the seven terrain glyphs is kept from the default.
+    pub fn preset(preset: ThemePreset) -> Self {
+        let [grass, tree, road, car, water, pad, player] = match preset {
+            ThemePreset::Day => return Self::default(),
+            ThemePreset::Night => ['🌑', '🌲', '🖤', '🚗', '🌊', '🔵', '🐸'],
+            ThemePreset::Desert => ['🟨', '🌵', '🟫', '🚙', '🟤', '🪨', '🐸'],
+            ThemePreset::Snow => ['⬜', '🎄', '⬛', '🚗', '🧊', '🧊', '🐸'],
+        };
+        let border_color = match preset {
+            ThemePreset::Day => 34,
+            ThemePreset::Night => 61,
+            ThemePreset::Desert => 178,
+            ThemePreset::Snow => 153,
+        };
         Self {
-            objects,
-            object_label,
-            environment_label,
+            grass,
+            tree,
+            road,
+            car,
+            water,
+            pad,
+            player,
+            border_color,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_last_life_glyph(mut self, player_last_life: char) -> Result<Self, ThemeError> {
+        self.player_last_life = player_last_life;
+        self.validate()?;
+        Ok(self)
+    }
+
+    pub fn with_cell_width(mut self, cell_width: usize) -> Result<Self, ThemeError> {
+        self.cell_width = Some(cell_width);
+        self.validate()?;
+        Ok(self)
+    }
+
+    fn glyphs(&self) -> [char; 16] {
+        [
+            self.grass,
+            self.tree,
+            self.road,
+            self.car,
+            self.water,
+            self.pad,
+            self.ice,
+            self.player,
+            self.player_last_life,
+            self.red_light,
+            self.green_light,
+            self.coin,
+            self.power_up,
+            self.train,
+            self.npc,
+            self.rock,
+        ]
+    }
+
+    // Without a cell_width every glyph must be the same width, otherwise each must fit inside it
+    pub fn validate(&self) -> Result<(), ThemeError> {
+        let expected = measure_text_width(&self.grass.to_string());
+        for glyph in self.glyphs() {
+            let width = measure_text_width(&glyph.to_string());
+            match self.cell_width {
+                Some(cell_width) if width > cell_width => {
+                    return Err(ThemeError::TooWide {
+                        glyph,
+                        width,
+                        cell_width,
+                    })
+                }
+                None if width != expected => {
+                    return Err(ThemeError::MixedWidths {
+                        glyph,
+                        width,
+                        expected,
+                    })
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn cell_width(&self) -> usize {
+        self.cell_width
+            .unwrap_or_else(|| measure_text_width(&self.grass.to_string()))
+    }
+
+    pub fn glyph(&self, label: char) -> char {
+        match label {
+            GRASS => self.grass,
+            TREE => self.tree,
+            ROAD => self.road,
+            CAR => self.car,
+            // themes with their own car draw every vehicle as it
+            TAXI | SUV | BUS if self.car != CAR => self.car,
+            WATER => self.water,
+            PAD => self.pad,
+            ICE => self.ice,
+            FROG => self.player,
+            HURT_FROG => self.player_last_life,
+            RED_LIGHT => self.red_light,
+            GREEN_LIGHT => self.green_light,
+            COIN => self.coin,
+            POWER_UP => self.power_up,
+            TRAIN => self.train,
+            NPC => self.npc,
+            ROCK => self.rock,
+            other => other,
         }
     }
+
+    // Pads a glyph out to the cell width so mixed-width themes stay aligned
+    pub fn cell(&self, label: char) -> String {
+        let glyph = self.glyph(label).to_string();
+        pad_str(&glyph, self.cell_width(), Alignment::Left, None).into_owned()
+    }
 }
 
-#[derive(Debug)]
-pub struct DynamicRow {
-    row: BaseRow,
-    direction: bool,
-    interval: u8,
-    tick_count: u8,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
 }
 
-impl DynamicRow {
-    pub fn new(row: BaseRow, direction: bool, interval: u8) -> Self {
-        Self {
-            row,
-            direction,
-            interval,
-            tick_count: 0,
+// A hint's moves, each with the cell it is made from
+pub type HintPath = Vec<((usize, usize), Direction)>;
+
+impl Direction {
+    // Drawn over the board to show a hint's path
+    pub fn arrow(self) -> char {
+        match self {
+            Direction::Up => '↑',
+            Direction::Down => '↓',
+            Direction::Left => '←',
+            Direction::Right => '→',
         }
     }
-    pub fn tick(&mut self) {
-        self.tick_count += 1;
-        if self.tick_count >= self.interval {
-            self.tick_count = 0;
+}
+
+// Which keys do what. The default is WASD plus the arrows, p or escape to pause and q to quit,
+// which only works while paused. 1-3 save to a slot and shift+1-3 load it back, f logs the
+// board's fingerprint, u or backspace undoes and h shows a hint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    pub up: Vec<Key>,
+    pub down: Vec<Key>,
+    pub left: Vec<Key>,
+    pub right: Vec<Key>,
+    pub pause: Vec<Key>,
+    pub quit: Vec<Key>,
+    // one key per quick save slot, in slot order
+    pub save: Vec<Key>,
+    pub load: Vec<Key>,
+    pub fingerprint: Vec<Key>,
+    pub undo: Vec<Key>,
+    pub hint: Vec<Key>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self {
+            up: vec![Key::Char('w'), Key::ArrowUp],
+            down: vec![Key::Char('s'), Key::ArrowDown],
+            left: vec![Key::Char('a'), Key::ArrowLeft],
+            right: vec![Key::Char('d'), Key::ArrowRight],
+            pause: vec![Key::Char('p'), Key::Escape],
+            quit: vec![Key::Char('q')],
+            save: vec![Key::Char('1'), Key::Char('2'), Key::Char('3')],
+            load: vec![Key::Char('!'), Key::Char('@'), Key::Char('#')],
+            fingerprint: vec![Key::Char('f')],
+            undo: vec![Key::Char('u'), Key::Backspace],
+            hint: vec![Key::Char('h')],
         }
-            if self.direction {
-                self.row
-                    .objects
-                    .insert(0, self.row.objects.clone().pop().unwrap());
+    }
+}
+
+impl KeyMap {
+    // CROSSY_KEY_UP=k or CROSSY_KEY_QUIT=x,esc replaces that action's keys. Keys are single
+    // characters or one of up, down, left, right, esc, enter, space and backspace. The save and
+    // load keys are listed in slot order.
+    pub fn from_env() -> Self {
+        let mut key_map = Self::default();
+        let bindings = [
+            ("CROSSY_KEY_UP", &mut key_map.up),
+            ("CROSSY_KEY_DOWN", &mut key_map.down),
+            ("CROSSY_KEY_LEFT", &mut key_map.left),
+            ("CROSSY_KEY_RIGHT", &mut key_map.right),
+            ("CROSSY_KEY_PAUSE", &mut key_map.pause),
+            ("CROSSY_KEY_QUIT", &mut key_map.quit),
+            ("CROSSY_KEY_SAVE", &mut key_map.save),
+            ("CROSSY_KEY_LOAD", &mut key_map.load),
+            ("CROSSY_KEY_FINGERPRINT", &mut key_map.fingerprint),
+            ("CROSSY_KEY_UNDO", &mut key_map.undo),
+            ("CROSSY_KEY_HINT", &mut key_map.hint),
+        ];
+        for (var, keys) in bindings {
+            let Ok(value) = std::env::var(var) else {
+                continue;
+            };
+            let parsed: Vec<Key> = value.split(',').filter_map(parse_key_name).collect();
+            if parsed.is_empty() {
+                eprintln!("Ignoring {}: no recognised keys in {:?}", var, value);
             } else {
-                self.row
-                    .objects
-                    .push(self.row.objects.clone().remove(0));
+                *keys = parsed;
             }
+        }
+        key_map
+    }
+
+    pub fn classify(&self, key: &Key) -> Option<Direction> {
+        [
+            (&self.up, Direction::Up),
+            (&self.down, Direction::Down),
+            (&self.left, Direction::Left),
+            (&self.right, Direction::Right),
+        ]
+        .into_iter()
+        .find(|(keys, _)| keys.contains(key))
+        .map(|(_, direction)| direction)
+    }
+
+    // What a key press means to the game. A key bound to both a move and something else does
+    // the other thing; None for keys that aren't bound at all.
+    pub fn action(&self, key: &Key) -> Option<Action> {
+        let slot = |keys: &[Key]| keys.iter().position(|bound| bound == key);
+        if self.pause.contains(key) {
+            Some(Action::Pause)
+        } else if self.quit.contains(key) {
+            Some(Action::Quit)
+        } else if let Some(slot) = slot(&self.save) {
+            Some(Action::Save(slot))
+        } else if let Some(slot) = slot(&self.load) {
+            Some(Action::Load(slot))
+        } else if self.fingerprint.contains(key) {
+            Some(Action::Fingerprint)
+        } else if self.undo.contains(key) {
+            Some(Action::Undo)
+        } else if self.hint.contains(key) {
+            Some(Action::Hint)
+        } else {
+            self.classify(key).map(Action::Move)
+        }
     }
 }
 
-pub trait RowType: Debug {
-    fn get_base_row(&self) -> &BaseRow;
-    fn tick(&mut self) -> Option<bool>;
-    fn check_position(&self, column_index: usize) -> Option<bool>;
+fn parse_key_name(name: &str) -> Option<Key> {
+    let name = name.trim();
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(Key::Char(c));
+    }
+    match name.to_lowercase().as_str() {
+        "up" => Some(Key::ArrowUp),
+        "down" => Some(Key::ArrowDown),
+        "left" => Some(Key::ArrowLeft),
+        "right" => Some(Key::ArrowRight),
+        "esc" | "escape" => Some(Key::Escape),
+        "enter" => Some(Key::Enter),
+        "space" => Some(Key::Char(' ')),
+        "backspace" => Some(Key::Backspace),
+        _ => None,
+    }
 }
 
-#[derive(Debug)]
-pub struct Stream {
-    pub dynamic_row: DynamicRow,
+// The kind of the newest row and how many rows of that kind are stacked up in a row there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RowHistory {
+    pub last_type: Option<RowKind>,
+    pub consecutive_count: u8,
 }
 
-impl Stream {
-    pub fn new(objects: Vec<bool>, interval: u8, direction: bool) -> Self {
-        Self {
-            dynamic_row: DynamicRow::new(BaseRow::new(objects, PAD, WATER), direction, interval),
+impl RowHistory {
+    // The history of a stack of rows listed bottom row first
+    pub fn of(kinds: &[RowKind]) -> Self {
+        let mut history = Self::default();
+        for &kind in kinds {
+            history.push(kind);
+        }
+        history
+    }
+
+    pub fn push(&mut self, kind: RowKind) {
+        if self.last_type == Some(kind) {
+            self.consecutive_count = self.consecutive_count.saturating_add(1);
+        } else {
+            self.last_type = Some(kind);
+            self.consecutive_count = 1;
         }
     }
 }
 
-impl RowType for Stream {
-    fn get_base_row(&self) -> &BaseRow {
-        &self.dynamic_row.row
+// The vehicle length and count for a road of `width` cells that should hold at least `min_cars`
+// cars. Short of the minimum, whole vehicles are added; where that many won't fit with a gap
+// after each, the vehicles get longer instead. A road always keeps one open cell, so it holds
+// at most width - 1 cars.
+pub fn vehicle_groups(width: usize, min_cars: usize, size: usize, groups: usize) -> (usize, usize) {
+    let size = size.max(1);
+    let groups = groups.max(min_cars.div_ceil(size));
+    if groups == 0 {
+        return (size, 0);
     }
-    fn tick(&mut self) -> Option<bool> {
-        Some(self.dynamic_row.direction)
+    (1..=groups)
+        .rev()
+        .map(|count| (size.max(min_cars.div_ceil(count)), count))
+        .find(|&(size, count)| count * (size + 1) <= width)
+        .unwrap_or((width.saturating_sub(1).max(1), 1))
+}
+
+// Picks the next generated row's kind uniformly, except that a stream is always followed by
+// grass and so is a second road in a row
+pub fn choose_row_kind(history: RowHistory, rng: &mut impl Rng) -> RowKind {
+    match (history.last_type, history.consecutive_count) {
+        (Some(RowKind::Stream), count) if count >= 1 => return RowKind::Grass,
+        (Some(RowKind::Road), count) if count >= 2 => return RowKind::Grass,
+        _ => {}
     }
-    fn check_position(&self, column_index: usize) -> Option<bool> {
-        Some(self.dynamic_row.row.objects[column_index])
+    match rng.gen_range(0..=2) {
+        0 => RowKind::Stream,
+        1 => RowKind::Road,
+        _ => RowKind::Grass,
     }
 }
 
-#[derive(Debug)]
-pub struct Road {
-    pub dynamic_row: DynamicRow,
+// Resolves several moves pressed in the same frame to one. Opposite directions cancel out,
+// then forward beats sideways and sideways beats backwards.
+pub fn resolve_inputs(pressed: &[Direction]) -> Option<Direction> {
+    let has = |direction| pressed.contains(&direction);
+    let vertical = match (has(Direction::Up), has(Direction::Down)) {
+        (true, false) => Some(Direction::Up),
+        (false, true) => Some(Direction::Down),
+        _ => None,
+    };
+    let lateral = match (has(Direction::Left), has(Direction::Right)) {
+        (true, false) => Some(Direction::Left),
+        (false, true) => Some(Direction::Right),
+        _ => None,
+    };
+    match (vertical, lateral) {
+        (Some(Direction::Up), _) => Some(Direction::Up),
+        (_, Some(lateral)) => Some(lateral),
+        (vertical, None) => vertical,
+    }
 }
 
-impl Road {
-    pub fn new(objects: Vec<bool>, interval: u8, direction: bool) -> Self {
-        Self {
-            dynamic_row: DynamicRow::new(BaseRow::new(objects, CAR, ROAD), direction, interval),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DifficultyParams {
+    // index into DIFFICULTY_THRESHOLDS
+    pub level: usize,
+    pub threshold: u32,
+    // None once the last level is reached
+    pub next_threshold: Option<u32>,
+}
+
+impl DifficultyParams {
+    // How far the score is from this level's threshold to the next, from 0.0 to 1.0
+    pub fn progress(&self, score: u32) -> f64 {
+        match self.next_threshold {
+            Some(next) => {
+                let span = next.saturating_sub(self.threshold).max(1);
+                (score.saturating_sub(self.threshold) as f64 / span as f64).min(1.0)
+            }
+            None => 1.0,
         }
     }
 }
 
-impl RowType for Road {
-    fn get_base_row(&self) -> &BaseRow {
-        &self.dynamic_row.row
+pub fn difficulty_params(score: u32) -> DifficultyParams {
+    let level = DIFFICULTY_THRESHOLDS
+        .iter()
+        .rposition(|&threshold| score >= threshold)
+        .unwrap_or(0);
+    DifficultyParams {
+        level,
+        threshold: DIFFICULTY_THRESHOLDS[level],
+        next_threshold: DIFFICULTY_THRESHOLDS.get(level + 1).copied(),
     }
-    fn tick(&mut self) -> Option<bool> {
-        self.dynamic_row.tick();
-        None
+}
+
+// A bar of `width` columns filled to `fraction`, using eighth blocks for the partly filled cell
+pub fn progress_bar(fraction: f64, width: usize) -> String {
+    const PARTIAL: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+    let eighths = (fraction.clamp(0.0, 1.0) * (width * 8) as f64).round() as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    if eighths / 8 < width {
+        bar.push(PARTIAL[eighths % 8]);
+        bar.push_str(&" ".repeat(width - eighths / 8 - 1));
     }
-    fn check_position(&self, column_index: usize) -> Option<bool> {
-        Some(self.dynamic_row.row.objects[column_index])
+    bar
+}
+
+// Builds a random config and move list from one seed, so a failing case is reproduced by
+// rerunning its seed
+pub fn fuzz_case(seed: u64) -> (GameConfig, Vec<Direction>) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut config = GameConfig {
+        seed: Some(rng.gen()),
+        lives: rng.gen_range(1..=5),
+        ice_rows: rng.gen(),
+        two_step_rivers: rng.gen(),
+        safe_landing_assist: rng.gen(),
+        random_start_column: rng.gen(),
+        invincible: rng.gen_bool(0.2),
+        startup_grace_ticks: rng.gen_range(0..=20),
+        invincibility_ticks: rng.gen_range(0..=60),
+        min_cars_per_road: rng.gen_range(0..=14),
+        viewport_width: rng.gen_bool(0.3).then(|| rng.gen_range(1..=14)),
+        config_dir: None,
+        data_dir: None,
+        ..GameConfig::default()
+    };
+    config.bottom_edge = match rng.gen_range(0..3) {
+        0 => EdgeBehavior::Block,
+        1 => EdgeBehavior::Die,
+        _ => EdgeBehavior::Nothing,
+    };
+    if rng.gen_bool(0.3) {
+        config.board = BoardConfig {
+            width: rng.gen_range(1..=20),
+            height: rng.gen_range(2..=10),
+            ..BoardConfig::default()
+        };
+    }
+    if rng.gen_bool(0.2) {
+        config.wrap_world = Some(rng.gen_range(1..=30));
+    } else if rng.gen_bool(0.2) {
+        config.level = Some(LevelConfig {
+            length: rng.gen_range(0..=40),
+            object_budget: rng.gen_range(0..=300),
+        });
     }
+    let moves = (0..rng.gen_range(0..=500))
+        .map(|_| match rng.gen_range(0..6) {
+            0..=2 => Direction::Up,
+            3 => Direction::Down,
+            4 => Direction::Left,
+            _ => Direction::Right,
+        })
+        .collect();
+    (config, moves)
 }
 
-#[derive(Debug)]
-pub struct Grass {
-    pub baserow: BaseRow,
+// Density curve peaking at 0.35 in the middle columns and falling to 0.05 at the edges
+pub fn center_weighted_density(column: usize, width: usize) -> f64 {
+    if width < 2 {
+        return 0.2;
+    }
+    let middle = (width - 1) as f64 / 2.0;
+    let distance = (column as f64 - middle).abs() / middle;
+    0.35 - 0.3 * distance
 }
 
-impl Grass {
-    pub fn new(objects: Vec<bool>) -> Self {
-        Self {
-            baserow: BaseRow::new(objects, TREE, GRASS),
-        }
+// Loop interval during the warmup: starts at twice the target and eases linearly down to it
+pub fn warmup_interval(target: Duration, warmup: Duration, elapsed: Duration) -> Duration {
+    if elapsed >= warmup {
+        return target;
     }
+    let remaining = 1.0 - elapsed.as_secs_f64() / warmup.as_secs_f64();
+    target + target.mul_f64(remaining)
 }
 
-impl RowType for Grass {
-    fn get_base_row(&self) -> &BaseRow {
-        &self.baserow
+// Fixed input script for benchmarking: the same seed always yields the same (tick, move) list,
+// mostly advancing with some sideways and backwards moves mixed in
+pub fn bench_scenario(seed: u64, ticks: u64) -> Vec<(u64, Direction)> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut script = Vec::new();
+    for tick in 0..ticks {
+        if rng.gen_bool(0.5) {
+            continue;
+        }
+        let direction = match rng.gen_range(0..10) {
+            0..=4 => Direction::Up,
+            5 | 6 => Direction::Left,
+            7 | 8 => Direction::Right,
+            _ => Direction::Down,
+        };
+        script.push((tick, direction));
     }
-    fn tick(&mut self) -> Option<bool> {
-        None
+    script
+}
+
+// Where scores and stats are kept between sessions
+pub fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join("crossy_roads"))
+}
+
+pub fn data_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| {
+        PathBuf::from(home)
+            .join(".local")
+            .join("share")
+            .join("crossy_roads")
+    })
+}
+
+// What happened during one game, shown on the game over screen and logged one line per session
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionStats {
+    pub total_ticks: u64,
+    pub rows_crossed: u32,
+    pub times_hit_by_car: u32,
+    pub times_drowned: u32,
+    pub trees_blocked_by: u32,
+    pub powerups_collected: u32,
+    pub max_combo: u32,
+    #[serde(default)]
+    pub coins_collected: u32,
+    // cars on each road the frog has hopped off the far side of
+    #[serde(default)]
+    pub cars_dodged: u32,
+}
+
+impl SessionStats {
+    pub fn log_path(data_dir: &Path) -> PathBuf {
+        data_dir.join("sessions.jsonl")
     }
-    fn check_position(&self, column_index: usize) -> Option<bool> {
-        Some(self.baserow.objects[column_index])
+
+    pub fn append_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(file, "{}", serde_json::to_string(self)?)
     }
 }
 
-pub struct GameState {
-    gameboard: Vec<Box<dyn RowType>>,
-    player: (usize, usize),
-    keyreader: KeyReader,
-    player_score: u32,
+// Stats that carry over between sessions
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PersistentStats {
+    pub longest_streak: u64,
+    pub games_played: u32,
 }
 
-impl GameState {
-    pub fn new() -> Self {
-        let mut bottom_row = BaseRow::randomized_objects(TREE, GRASS);
-        bottom_row.objects[7] = false;
-        Self {
-            gameboard: vec![
-                Box::new(Grass::new(bottom_row.objects)),
-                Box::new(Grass::new(BaseRow::randomized_objects(TREE, GRASS).objects)),
-                GameState::create_random_row(None),
-                GameState::create_random_row(None),
-                GameState::create_random_row(None),
-                GameState::create_random_row(None),
-                GameState::create_random_row(None),
-            ],
-            player: (7, 0),
-            keyreader: KeyReader::new(),
-            player_score: 0,
+impl PersistentStats {
+    pub fn path(config_dir: &Path) -> PathBuf {
+        config_dir.join("stats.json")
+    }
+
+    // A missing or unreadable file just means no games have been recorded yet
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
         }
+        fs::write(path, serde_json::to_string_pretty(self)?)
     }
 
-    // Update stack will create random row, remove first row, and push new row
-    pub fn create_random_row(previous_row: Option<&BaseRow>) -> Box<dyn RowType> {
-        let mut rng = rand::thread_rng();
-        let row_type = rng.gen_range(0..=2);
-        let interval = rng.gen_range(1..=5);
-        let direction = rng.gen_bool(0.5);
-        let objects = BaseRow::randomized_objects(TREE, GRASS).objects;
+    pub fn record_run(&mut self, ticks_survived: u64) {
+        self.games_played += 1;
+        self.longest_streak = self.longest_streak.max(ticks_survived);
+    }
+}
+
+// Whether equalling the best score counts as a new high score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HighScorePolicy {
+    #[default]
+    Beat,
+    Match,
+}
+
+// How scoreboard entries with the same score are ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieBreak {
+    #[default]
+    EarliestFirst,
+    LongestSurvival,
+}
 
-        match row_type {
-            0 => Box::new(Stream::new(objects, interval, direction)),
-            1 => Box::new(Road::new(objects, interval, direction)),
-            _ => Box::new(Grass::new(objects)),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
         }
     }
+}
 
-    pub fn print_gameboard(&self) {
-        let term = Term::stdout();
-        term.clear_screen().unwrap();
-        let player_row_index = self.player.1;
+// Quotes a CSV field only when it needs it, doubling any embedded quotes
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
 
-        for (row_index, row) in self.gameboard.iter().enumerate().rev() {
-            for (col_index, &obj) in row.get_base_row().objects.iter().enumerate() {
-                if row_index == player_row_index && col_index == self.player.0 {
-                    print!("🐸");
-                } else {
-                    print!(
-                        "{}",
-                        if obj {
-                            row.get_base_row().object_label
-                        } else {
-                            row.get_base_row().environment_label
-                        }
-                    );
-                }
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
             }
-            println!();
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(String::new()),
+            (c, _) => fields.last_mut().unwrap().push(c),
         }
-        println!("Score: {}", self.player_score);
     }
+    fields
+}
 
-    pub async fn tick(&mut self, key: Option<Key>) {
-        self.gameboard.iter_mut().for_each(|row| {
-            row.tick();
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScoreEntry {
+    pub score: u32,
+    pub ticks_survived: u64,
+    // seconds since the unix epoch
+    pub achieved_at: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scoreboard {
+    pub entries: Vec<ScoreEntry>,
+}
+
+impl Scoreboard {
+    pub const MAX_ENTRIES: usize = 10;
+
+    pub fn path(config_dir: &Path) -> PathBuf {
+        config_dir.join("scores.json")
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn high_score(&self) -> Option<u32> {
+        self.entries.iter().map(|entry| entry.score).max()
+    }
+
+    pub fn is_new_high_score(&self, score: u32, policy: HighScorePolicy) -> bool {
+        match (self.high_score(), policy) {
+            (None, _) => score > 0,
+            (Some(best), HighScorePolicy::Beat) => score > best,
+            (Some(best), HighScorePolicy::Match) => score >= best,
+        }
+    }
+
+    // Highest score first, equal scores ordered by the tie-break and then by who got there first
+    pub fn record(&mut self, entry: ScoreEntry, tie_break: TieBreak) {
+        self.entries.push(entry);
+        self.entries.sort_by(|a, b| {
+            b.score.cmp(&a.score).then_with(|| match tie_break {
+                TieBreak::EarliestFirst => a.achieved_at.cmp(&b.achieved_at),
+                TieBreak::LongestSurvival => b
+                    .ticks_survived
+                    .cmp(&a.ticks_survived)
+                    .then_with(|| a.achieved_at.cmp(&b.achieved_at)),
+            })
         });
-            if key.is_some() && self.update_player(key.unwrap()).await {
-            self.update_stack();
-        } 
-        // check the updated player position for legality
-        // bounce back if needed
+        self.entries.truncate(Self::MAX_ENTRIES);
     }
 
-    pub fn update_stack(&mut self) {
-        self.gameboard.remove(0);
-        self.gameboard.push(GameState::create_random_row(None));
-        println!("Updated stack");
+    // Columns are always written in this order so exports diff cleanly
+    const CSV_HEADER: [&'static str; 3] = ["score", "ticks_survived", "achieved_at"];
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = Self::CSV_HEADER.join(",");
+        csv.push('\n');
+        for entry in &self.entries {
+            let fields = [
+                entry.score.to_string(),
+                entry.ticks_survived.to_string(),
+                entry.achieved_at.to_string(),
+            ];
+            let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
     }
 
-    pub async fn run(&mut self) {
-        loop {
-            self.print_gameboard();
-            if let Some(key) = self.keyreader.read_key().await {
-                self.tick(Some(key)).await;
-            } else {
-                self.tick(None).await;
-            }
-            sleep(Duration::from_millis(50)).await;
+    pub fn from_csv(csv: &str) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut lines = csv.lines();
+        let header = parse_csv_line(lines.next().unwrap_or_default());
+        if header != Self::CSV_HEADER {
+            return Err(invalid(format!("unexpected csv header {:?}", header)));
         }
+        let mut entries = Vec::new();
+        for (index, line) in lines.enumerate().filter(|(_, line)| !line.is_empty()) {
+            let line_number = index + 2;
+            let fields = parse_csv_line(line);
+            let number = |column: usize| -> io::Result<u64> {
+                let name = Self::CSV_HEADER[column];
+                fields
+                    .get(column)
+                    .and_then(|field| field.parse().ok())
+                    .ok_or_else(|| invalid(format!("line {}: bad {}", line_number, name)))
+            };
+            entries.push(ScoreEntry {
+                score: number(0)?
+                    .try_into()
+                    .map_err(|_| invalid(format!("line {}: score out of range", line_number)))?,
+                ticks_survived: number(1)?,
+                achieved_at: number(2)?,
+            });
+        }
+        Ok(Self { entries })
     }
 
-    pub async fn update_player(&mut self, key: Key) -> bool {
-            match key {
-                Key::Char('w') | Key::ArrowUp => {
-                    if self.player.1 < 3 {
-                        self.player.1 += 1;
-                    }
-                    return true;
-                }
-                Key::Char('s') | Key::ArrowDown => {
-                    if self.player.1 > 0 {
-                        self.player.1 -= 1;
-                    }
-                    return false;
-                }
-                Key::Char('a') | Key::ArrowLeft => {
-                    if self.player.0 > 0 {
-                        self.player.0 -= 1;
-                    }
-                    return false;
-                }
-                Key::Char('d') | Key::ArrowRight => {
-                    if self.player.0 < 14 {
-                        self.player.0 += 1;
-                    }
-                    return false;
-                }
-                _ => return false,
-            }
-    } 
+    pub fn export(&self, path: &Path, format: ExportFormat) -> io::Result<()> {
+        let contents = match format {
+            ExportFormat::Csv => self.to_csv(),
+            ExportFormat::Json => serde_json::to_string_pretty(self)?,
+        };
+        fs::write(path, contents)
+    }
+
+    pub fn import(path: &Path, format: ExportFormat) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        match format {
+            ExportFormat::Csv => Self::from_csv(&contents),
+            ExportFormat::Json => Ok(serde_json::from_str(&contents)?),
+        }
+    }
+}
+
+// The scoreboard as the game uses it: loaded once at startup and written back whenever a run
+// is recorded. Without a config directory it still works, just without saving.
+#[derive(Debug, Clone, Default)]
+pub struct HighScoreStore {
+    path: Option<PathBuf>,
+    scoreboard: Scoreboard,
 }
 
+impl HighScoreStore {
+    // The scores kept in config_dir, which is created if it doesn't exist yet
+    pub fn load(config_dir: Option<&Path>) -> Self {
+        if let Some(dir) = config_dir {
+            let _ = fs::create_dir_all(dir);
+        }
+        Self::load_from(config_dir.map(Scoreboard::path))
+    }
+
+    pub fn load_from(path: Option<PathBuf>) -> Self {
+        let scoreboard = path
+            .as_deref()
+            .map(Scoreboard::load_from)
+            .unwrap_or_default();
+        Self { path, scoreboard }
+    }
+
+    pub fn high_score(&self) -> Option<u32> {
+        self.scoreboard.high_score()
+    }
 
+    // Top scores, best first
+    pub fn scores(&self) -> Vec<u32> {
+        self.scoreboard
+            .entries
+            .iter()
+            .map(|entry| entry.score)
+            .collect()
+    }
 
-#[tokio::main]
-async fn main() {
-    let mut game_state = GameState::new();
-    game_state.run().await;
+    pub fn scoreboard(&self) -> &Scoreboard {
+        &self.scoreboard
+    }
+
+    pub fn record(&mut self, entry: ScoreEntry, tie_break: TieBreak) -> io::Result<()> {
+        self.scoreboard.record(entry, tie_break);
+        match &self.path {
+            Some(path) => self.scoreboard.save_to(path),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub tick: u64,
+    pub input: Option<Direction>,
+    pub player: (usize, usize),
+    pub score: u32,
+}
+
+// Per-tick record of a run, written out on game over
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replay {
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn load_from(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayDiff {
+    // First tick where the player positions differ, or where one run ended before the other
+    pub first_divergence: Option<u64>,
+    // (tick, b's score minus a's score) for every tick both runs reached
+    pub score_deltas: Vec<(u64, i64)>,
+}
+
+pub fn diff_replays(a: &Replay, b: &Replay) -> ReplayDiff {
+    let mut first_divergence = None;
+    let mut score_deltas = Vec::new();
+    for (frame_a, frame_b) in a.frames.iter().zip(&b.frames) {
+        if first_divergence.is_none() && frame_a.player != frame_b.player {
+            first_divergence = Some(frame_a.tick);
+        }
+        score_deltas.push((frame_a.tick, frame_b.score as i64 - frame_a.score as i64));
+    }
+    if first_divergence.is_none() && a.frames.len() != b.frames.len() {
+        let shorter = a.frames.len().min(b.frames.len());
+        first_divergence = a
+            .frames
+            .get(shorter)
+            .or(b.frames.get(shorter))
+            .map(|f| f.tick);
+    }
+    ReplayDiff {
+        first_divergence,
+        score_deltas,
+    }
+}
+
+// Every key the game was given, by frame, plus the seed it started from. Unlike Replay this
+// is enough to play the whole run again, save slots and pauses included.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayRecorder {
+    pub seed: u64,
+    // only frames that had input; the rest were idle
+    pub inputs: Vec<(u64, Action)>,
+    pub ticks: u64,
+    pub final_score: u32,
+}
+
+impl ReplayRecorder {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Self::default()
+        }
+    }
+
+    pub fn record(&mut self, action: Action) {
+        if action != Action::Idle {
+            self.inputs.push((self.ticks, action));
+        }
+        self.ticks += 1;
+    }
+}
+
+// Plays a recording back headlessly. The game must be set up the same way it was recorded,
+// which for anything but the default config means passing it to with_config.
+#[derive(Debug, Clone)]
+pub struct ReplayPlayer {
+    pub recording: ReplayRecorder,
+    config: GameConfig,
+}
+
+impl ReplayPlayer {
+    pub fn new(recording: ReplayRecorder) -> Self {
+        Self {
+            recording,
+            config: GameConfig::default(),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Self::new(serde_json::from_str(&contents)?))
+    }
+
+    pub fn with_config(mut self, config: GameConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    // Panics if the run ends on a different score than the recorded one
+    pub fn run(&self) -> GameResult {
+        let mut state = GameState::headless(GameConfig {
+            seed: Some(self.recording.seed),
+            ..self.config.clone()
+        });
+        let mut actions = vec![Action::Idle; self.recording.ticks as usize];
+        for &(tick, action) in &self.recording.inputs {
+            if let Some(slot) = actions.get_mut(tick as usize) {
+                *slot = action;
+            }
+        }
+        let result = state.run_headless(actions);
+        assert_eq!(
+            result.score, self.recording.final_score,
+            "replay of seed {} ended on a different score",
+            self.recording.seed
+        );
+        result
+    }
+}
+
+// One `tick column,row input board_hash` line per frame, rotated to `<path>.1` once it gets big
+#[derive(Debug)]
+pub struct TraceLog {
+    path: PathBuf,
+    file: io::BufWriter<fs::File>,
+    bytes: u64,
+}
+
+impl TraceLog {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = fs::File::create(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: io::BufWriter::new(file),
+            bytes: 0,
+        })
+    }
+
+    pub fn record(
+        &mut self,
+        tick: u64,
+        player: (usize, usize),
+        input: Option<Direction>,
+        board_hash: u64,
+    ) -> io::Result<()> {
+        if self.bytes >= TRACE_MAX_BYTES {
+            self.file.flush()?;
+            let mut rotated = self.path.clone().into_os_string();
+            rotated.push(".1");
+            fs::rename(&self.path, rotated)?;
+            self.file = io::BufWriter::new(fs::File::create(&self.path)?);
+            self.bytes = 0;
+        }
+        let line = format!(
+            "{} {},{} {:?} {:016x}\n",
+            tick, player.0, player.1, input, board_hash
+        );
+        self.file.write_all(line.as_bytes())?;
+        self.bytes += line.len() as u64;
+        Ok(())
+    }
+}
+
+// Reads keys on a background thread into a buffer, so presses between frames aren't lost.
+// Keys are read from the moment the reader is built, so presses made while the game is still
+// setting up are queued rather than lost.
+#[derive(Debug)]
+pub struct KeyReader {
+    keys: mpsc::Receiver<Key>,
+    // keys already taken off the channel by has_pending but not handed out yet
+    buffered: VecDeque<Key>,
+}
+
+impl Default for KeyReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyReader {
+    pub fn new() -> KeyReader {
+        let (sender, keys) = mpsc::channel(KEY_BUFFER);
+        // a plain thread rather than a tokio task so a pending read never holds up shutdown
+        std::thread::spawn(move || {
+            let term = Term::stdout();
+            while let Ok(key) = term.read_key() {
+                // without a terminal every read returns Unknown straight away
+                if key == Key::Unknown && !term.is_term() {
+                    break;
+                }
+                if sender.blocking_send(key).is_err() {
+                    break;
+                }
+            }
+        });
+        KeyReader {
+            keys,
+            buffered: VecDeque::new(),
+        }
+    }
+
+    // A reader that never yields a key, for driving the game without a terminal
+    pub fn idle() -> KeyReader {
+        let (_, keys) = mpsc::channel(1);
+        KeyReader {
+            keys,
+            buffered: VecDeque::new(),
+        }
+    }
+
+    pub async fn read_key(&mut self) -> Option<Key> {
+        match self.buffered.pop_front() {
+            Some(key) => Some(key),
+            None => self.keys.recv().await,
+        }
+    }
+
+    // Returns a key only if one has already been pressed, without waiting for input
+    pub fn poll_key(&mut self) -> Option<Key> {
+        self.buffered
+            .pop_front()
+            .or_else(|| self.keys.try_recv().ok())
+    }
+
+    // Every key pressed since the last call, oldest first
+    pub fn drain_keys(&mut self) -> Vec<Key> {
+        let mut keys: Vec<Key> = self.buffered.drain(..).collect();
+        while let Ok(key) = self.keys.try_recv() {
+            keys.push(key);
+        }
+        keys
+    }
+
+    // Whether a key is waiting, without consuming it
+    pub fn has_pending(&mut self) -> bool {
+        while let Ok(key) = self.keys.try_recv() {
+            self.buffered.push_back(key);
+        }
+        !self.buffered.is_empty()
+    }
+}
+
+// One frame's worth of input, wherever it came from. Keys only mean anything once a KeyMap
+// has turned them into one of these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    Move(Direction),
+    Pause,
+    // only while paused
+    Quit,
+    // quick save and load slots, counted from 0
+    Save(usize),
+    Load(usize),
+    Fingerprint,
+    Undo,
+    Hint,
+    // nothing pressed this frame
+    Idle,
+}
+
+pub type InputFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+// Where GameState::run gets its input from: the terminal, a script, a recorded replay, ...
+pub trait InputSource {
+    // None once the source has run dry, which ends the run
+    fn next_input(&mut self) -> InputFuture<'_, Option<Action>>;
+    // Only a person at a keyboard needs to be waited for before the world starts moving
+    fn interactive(&self) -> bool {
+        false
+    }
+    fn wait_until_ready(&mut self, _timeout: Duration) -> InputFuture<'_, ()> {
+        Box::pin(async {})
+    }
+}
+
+// Keys from the terminal, read the way the config asks for
+pub struct TerminalInput {
+    reader: KeyReader,
+    key_map: KeyMap,
+    // block until a key arrives instead of polling once per frame
+    wait: bool,
+    resolve_multi_keys: bool,
+}
+
+impl TerminalInput {
+    pub fn new(reader: KeyReader, key_map: KeyMap, config: &GameConfig) -> Self {
+        Self {
+            reader,
+            key_map,
+            wait: config.turn_based,
+            resolve_multi_keys: config.resolve_multi_keys,
+        }
+    }
+}
+
+impl InputSource for TerminalInput {
+    fn next_input(&mut self) -> InputFuture<'_, Option<Action>> {
+        Box::pin(async move {
+            // unbound keys still use up the frame, like a key that doesn't move the frog
+            if self.wait {
+                let key = self.reader.read_key().await?;
+                return Some(self.key_map.action(&key).unwrap_or(Action::Idle));
+            }
+            if !self.resolve_multi_keys {
+                let action = self
+                    .reader
+                    .poll_key()
+                    .and_then(|key| self.key_map.action(&key));
+                return Some(action.unwrap_or(Action::Idle));
+            }
+            let actions: Vec<Action> = self
+                .reader
+                .drain_keys()
+                .iter()
+                .filter_map(|key| self.key_map.action(key))
+                .collect();
+            let moves: Vec<Direction> = actions
+                .iter()
+                .filter_map(|action| match action {
+                    Action::Move(direction) => Some(*direction),
+                    _ => None,
+                })
+                .collect();
+            // anything that isn't a move, such as a save, is handled before any move
+            let other = actions
+                .into_iter()
+                .find(|action| !matches!(action, Action::Move(_)));
+            Some(
+                other
+                    .or_else(|| resolve_inputs(&moves).map(Action::Move))
+                    .unwrap_or(Action::Idle),
+            )
+        })
+    }
+
+    fn interactive(&self) -> bool {
+        true
+    }
+
+    // Keys pressed before the game started already show the player is there; they skip the
+    // wait and are played from the first tick. The key that ends a wait only starts the game.
+    fn wait_until_ready(&mut self, timeout: Duration) -> InputFuture<'_, ()> {
+        Box::pin(async move {
+            if !self.reader.has_pending() {
+                let _ = tokio::time::timeout(timeout, self.reader.read_key()).await;
+            }
+        })
+    }
+}
+
+// A fixed list of actions, one per frame
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedInput {
+    actions: VecDeque<Action>,
+}
+
+impl ScriptedInput {
+    pub fn new(actions: impl IntoIterator<Item = Action>) -> Self {
+        Self {
+            actions: actions.into_iter().collect(),
+        }
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn next_input(&mut self) -> InputFuture<'_, Option<Action>> {
+        Box::pin(async move { self.actions.pop_front() })
+    }
+}
+
+// Feeds a recorded run's inputs back in, frame by frame
+#[derive(Debug, Clone)]
+pub struct ReplayInput {
+    frames: VecDeque<ReplayFrame>,
+}
+
+impl ReplayInput {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            frames: replay.frames.into(),
+        }
+    }
+}
+
+impl InputSource for ReplayInput {
+    fn next_input(&mut self) -> InputFuture<'_, Option<Action>> {
+        Box::pin(async move {
+            let frame = self.frames.pop_front()?;
+            Some(frame.input.map_or(Action::Idle, Action::Move))
+        })
+    }
+}
+
+// Where a frog standing at `column` is once `row` has ticked: carried along with its pad on a
+// stream that just shifted, otherwise where it was. None means it was carried off the board.
+fn drifted_column(row: &dyn RowType, column: usize, width: usize) -> Option<usize> {
+    let Some(dynamic_row) = row
+        .dynamic_row()
+        .filter(|moving| row.carries_player() && moving.shifted())
+    else {
+        return Some(column);
+    };
+    // after the shift the pad that was under the frog sits one cell downstream
+    let (pad_column, target) = if dynamic_row.direction {
+        ((column + 1) % width, column + 1)
+    } else {
+        ((column + width - 1) % width, column.wrapping_sub(1))
+    };
+    if row.check_position(pad_column) != Some(true) {
+        return Some(column);
+    }
+    (target < width).then_some(target)
+}
+
+// Plays in place of the keyboard, choosing each tick's action from the current state
+pub trait Controller {
+    fn next_action(&mut self, state: &GameState) -> Action;
+}
+
+// Searches every way the frog could move over the next few ticks, playing copies of the rows
+// forward so moving cars and pads are where they will really be, and takes the first step of
+// the path that gets furthest up the board without dying. Idling counts as a move.
+#[derive(Debug, Clone)]
+pub struct AiController {
+    pub lookahead_ticks: usize,
+    pub lookahead_rows: usize,
+}
+
+impl Default for AiController {
+    fn default() -> Self {
+        Self {
+            lookahead_ticks: 12,
+            lookahead_rows: 5,
+        }
+    }
+}
+
+impl AiController {
+    pub fn best_move(&self, state: &GameState) -> Option<Direction> {
+        let width = state.board_width();
+        let (start_column, start_row) = state.player;
+        let top = (start_row + self.lookahead_rows + 1).min(state.gameboard.len());
+        let moves = [
+            Some(Direction::Up),
+            None,
+            Some(Direction::Left),
+            Some(Direction::Right),
+            Some(Direction::Down),
+        ];
+        let mut rows: Vec<Box<dyn RowType>> =
+            state.gameboard.iter().map(|row| row.clone_row()).collect();
+        // position -> the first move of the path that reached it, None until one is made
+        let mut frontier: BTreeMap<(usize, usize), Option<Option<Direction>>> =
+            BTreeMap::from([((start_column, start_row), None)]);
+        for _ in 0..self.lookahead_ticks {
+            rows.iter_mut().for_each(|row| {
+                row.tick();
+            });
+            let mut next = BTreeMap::new();
+            for (&(column, row_index), &first) in &frontier {
+                let Some(column) = drifted_column(rows[row_index].as_ref(), column, width) else {
+                    continue;
+                };
+                for direction in moves {
+                    // moves into the edge or a tree are left out, idling covers staying put
+                    let target = match direction {
+                        None => (column, row_index),
+                        Some(Direction::Up) if row_index + 1 < top => (column, row_index + 1),
+                        Some(Direction::Down) if row_index > 0 => (column, row_index - 1),
+                        Some(Direction::Left) if column > 0 => (column - 1, row_index),
+                        Some(Direction::Right) if column + 1 < width => (column + 1, row_index),
+                        _ => continue,
+                    };
+                    if rows[target.1].cell(target.0) != Some(Cell::Passable) {
+                        continue;
+                    }
+                    next.entry(target)
+                        .or_insert(Some(first.unwrap_or(direction)));
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+        frontier
+            .iter()
+            .max_by_key(|&(&(_, row_index), &first)| {
+                (row_index, first == Some(Some(Direction::Up)))
+            })
+            .and_then(|(_, &first)| first.flatten())
+    }
+}
+
+impl Controller for AiController {
+    fn next_action(&mut self, state: &GameState) -> Action {
+        self.best_move(state).map_or(Action::Idle, Action::Move)
+    }
+}
+
+// Where finished frames go. The game builds the text, renderers only decide where to put it.
+pub trait Renderer {
+    fn render_frame(&mut self, state: &GameState) -> io::Result<()>;
+    // Undo anything rendering did to the output, e.g. a hidden cursor
+    fn restore(&mut self) {}
+    // Boxes in a board `width` cells across, in the theme's border colour. Cells are measured
+    // in terminal columns, two for most emoji, and a row's edge glyph widens the box to fit.
+    fn draw_border(&self, board: &str, theme: &Theme, width: usize) -> String {
+        let inner = board
+            .lines()
+            .map(measure_text_width)
+            .fold(width * theme.cell_width(), usize::max);
+        let color = |text: String| style(text).color256(theme.border_color).to_string();
+        let mut bordered = color(format!("┌{}┐\n", "─".repeat(inner)));
+        for line in board.lines() {
+            bordered.push_str(&color("│".to_string()));
+            bordered.push_str(&pad_str(line, inner, Alignment::Left, None));
+            bordered.push_str(&color("│".to_string()));
+            bordered.push('\n');
+        }
+        bordered.push_str(&color(format!("└{}┘\n", "─".repeat(inner))));
+        bordered
+    }
+    // The bordered board with the HUD under it
+    fn compose_frame(&self, state: &GameState) -> String {
+        let board = state.render_board();
+        let mut frame = self.draw_border(&board, &state.theme, state.visible_columns().len());
+        frame.push_str(&self.render_hud(&state.current_hud(), &state.theme));
+        frame
+    }
+    // The lines under the board: score and lives, then feedback, metronome and pause lines
+    fn render_hud(&self, hud: &Hud, theme: &Theme) -> String {
+        let mut lines = String::new();
+        if hud.show_stats {
+            if let Some(remaining) = hud.rows_remaining {
+                lines.push_str(&format!("🏁 {} ROWS TO GO 🏁\n", remaining));
+            }
+            let clock = match hud.time_remaining {
+                Some(time_remaining) => format!(" | ⏱ {}s", time_remaining.as_secs()),
+                None => String::new(),
+            };
+            let wind = match hud.wind {
+                -1 => " | 💨←",
+                1 => " | 💨→",
+                _ => "",
+            };
+            let combo = match hud.combo {
+                Some(combo) => format!(" x{}🔥", combo),
+                None => String::new(),
+            };
+            let effects: String = hud
+                .effects
+                .iter()
+                .map(|kind| format!(" {}", kind.icon()))
+                .collect();
+            let stats = format!(
+                "Score: {} | Best: {} | Speed: {}{}{}{}{}",
+                hud.score, hud.best, hud.speed, clock, wind, combo, effects
+            );
+            lines.push_str(&format!("{}\n", style(stats).bold()));
+            let frog = theme.glyph(FROG).to_string();
+            lines.push_str(&format!("{}\n", frog.repeat(hud.lives as usize)));
+            if let Some(difficulty) = &hud.difficulty {
+                lines.push_str(&format!("{}\n", difficulty));
+            }
+        }
+        if let Some(feedback) = hud.feedback {
+            lines.push_str(&format!("{}\n", feedback));
+        }
+        if hud.metronome_pulse {
+            lines.push_str("🥁\n");
+        }
+        if hud.paused {
+            lines.push_str("PAUSED — press P to continue\n");
+        }
+        lines
+    }
+}
+
+// Everything the HUD shows, taken from the game each frame so renderers only lay it out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hud {
+    // score, lives and progress lines, off when the config hides the HUD
+    pub show_stats: bool,
+    pub score: u32,
+    pub best: u32,
+    pub lives: u8,
+    pub speed: u32,
+    pub time_remaining: Option<Duration>,
+    // -1, 0 or 1 as in GameState::wind_direction
+    pub wind: i8,
+    // only once it is above x1
+    pub combo: Option<u32>,
+    pub effects: Vec<PowerUpKind>,
+    pub rows_remaining: Option<u64>,
+    pub difficulty: Option<String>,
+    pub feedback: Option<&'static str>,
+    pub metronome_pulse: bool,
+    pub paused: bool,
+}
+
+pub struct TerminalRenderer {
+    term: Term,
+    // stdin's settings from before the game, since the key reader may be stuck mid-read in
+    // raw mode when the game ends
+    #[cfg(unix)]
+    original_mode: Option<libc::termios>,
+}
+
+impl TerminalRenderer {
+    pub fn new() -> Self {
+        Self {
+            term: Term::stdout(),
+            #[cfg(unix)]
+            original_mode: unsafe {
+                let mut mode = std::mem::zeroed();
+                (libc::tcgetattr(libc::STDIN_FILENO, &mut mode) == 0).then_some(mode)
+            },
+        }
+    }
+}
+
+impl Default for TerminalRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer for TerminalRenderer {
+    // The clear, the frame and the bell go out in a single write so rows never show up one
+    // at a time
+    fn render_frame(&mut self, state: &GameState) -> io::Result<()> {
+        let frame = self.compose_frame(state);
+        let mut buffer = String::with_capacity(CLEAR_SCREEN.len() + frame.len() + 1);
+        buffer.push_str(CLEAR_SCREEN);
+        buffer.push_str(&frame);
+        if state.metronome_pulse() && state.config.metronome_bell {
+            buffer.push('\x07');
+        }
+        self.term.write_str(&buffer)
+    }
+
+    fn restore(&mut self) {
+        let _ = self.term.show_cursor();
+        #[cfg(unix)]
+        if let Some(mode) = &self.original_mode {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, mode);
+            }
+        }
+    }
+}
+
+// Keeps only the latest frame, the way the terminal does after clearing the screen
+#[derive(Debug, Clone, Default)]
+pub struct StringRenderer {
+    pub buffer: String,
+}
+
+impl Renderer for StringRenderer {
+    fn render_frame(&mut self, state: &GameState) -> io::Result<()> {
+        self.buffer.clear();
+        self.buffer.push_str(&self.compose_frame(state));
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BaseRow {
+    objects: Vec<bool>,
+    object_label: char,
+    environment_label: char,
+}
+
+impl BaseRow {
+    pub fn new(objects: Vec<bool>, object_label: char, environment_label: char) -> Self {
+        Self {
+            objects,
+            object_label,
+            environment_label,
+        }
+    }
+    // Each cell holds an object with probability `density`, which must be in 0.0..=1.0
+    pub fn randomized_objects(
+        width: usize,
+        object_label: char,
+        environment_label: char,
+        density: f64,
+        rng: &mut impl Rng,
+    ) -> Self {
+        debug_assert!(
+            (0.0..=1.0).contains(&density),
+            "density {} is not a probability",
+            density
+        );
+        let mut objects = Vec::with_capacity(width);
+        for _ in 0..width {
+            objects.push(rng.gen_bool(density));
+        }
+        Self {
+            objects,
+            object_label,
+            environment_label,
+        }
+    }
+    // Like randomized_objects but sized and filled according to the board's preset
+    pub fn randomized_objects_with_cfg(
+        cfg: &BoardConfig,
+        object_label: char,
+        environment_label: char,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let density = cfg.object_density.clamp(0.0, 1.0);
+        Self::randomized_objects(cfg.width, object_label, environment_label, density, rng)
+    }
+    // `num_groups` runs of `group_size` objects at random spots, each followed by at least one
+    // empty cell (counting round the wrap) so runs never merge. Asking for more groups than fit
+    // gets as many as do.
+    pub fn randomized_objects_grouped(
+        width: usize,
+        object_label: char,
+        environment_label: char,
+        group_size: usize,
+        num_groups: usize,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let group_size = group_size.clamp(1, width.max(1));
+        let num_groups = num_groups.min(width / (group_size + 1));
+        let mut gaps = vec![1; num_groups];
+        for _ in 0..width - num_groups * (group_size + 1) {
+            if let Some(gap) = gaps.choose_mut(rng) {
+                *gap += 1;
+            }
+        }
+        let mut objects = Vec::with_capacity(width);
+        for gap in gaps {
+            objects.resize(objects.len() + group_size, true);
+            objects.resize(objects.len() + gap, false);
+        }
+        objects.resize(width, false);
+        objects.rotate_right(rng.gen_range(0..width.max(1)));
+        Self {
+            objects,
+            object_label,
+            environment_label,
+        }
+    }
+    // Like randomized_objects_grouped, but each run lands where the columns it covers are
+    // denser, and no run goes next to another. If the runs placed that way leave no room for
+    // the rest, the row is packed as randomized_objects_grouped does it instead, so a crowded
+    // road still gets every run.
+    pub fn randomized_objects_grouped_by_column(
+        width: usize,
+        object_label: char,
+        environment_label: char,
+        group_size: usize,
+        num_groups: usize,
+        density: impl Fn(usize) -> f64,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let group_size = group_size.clamp(1, width.max(1));
+        let mut objects = vec![false; width];
+        for _ in 0..num_groups {
+            // the run and a cell either side of it must all be empty, counting round the wrap
+            let starts: Vec<usize> = (0..width)
+                .filter(|&start| {
+                    (0..group_size + 2).all(|offset| !objects[(start + width - 1 + offset) % width])
+                })
+                .collect();
+            let weight = |&start: &usize| -> f64 {
+                (start..start + group_size)
+                    .map(|column| density(column % width).clamp(0.0, 1.0))
+                    .sum()
+            };
+            // a curve that is zero everywhere still places its runs, just anywhere
+            let chosen = starts.choose_weighted(&mut *rng, weight).ok();
+            let Some(&start) = chosen.or_else(|| starts.choose(rng)) else {
+                return Self::randomized_objects_grouped(
+                    width,
+                    object_label,
+                    environment_label,
+                    group_size,
+                    num_groups,
+                    rng,
+                );
+            };
+            for column in start..start + group_size {
+                objects[column % width] = true;
+            }
+        }
+        Self {
+            objects,
+            object_label,
+            environment_label,
+        }
+    }
+    pub fn object_count(&self) -> usize {
+        self.objects.iter().filter(|&&obj| obj).count()
+    }
+    // Fraction of cells holding an object
+    pub fn density(&self) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        self.object_count() as f64 / self.objects.len() as f64
+    }
+    // Like randomized_objects but the spawn chance of each cell comes from its column
+    pub fn randomized_objects_by_column(
+        width: usize,
+        object_label: char,
+        environment_label: char,
+        density: impl Fn(usize) -> f64,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let objects = (0..width)
+            .map(|column| rng.gen_bool(density(column).clamp(0.0, 1.0)))
+            .collect();
+        Self {
+            objects,
+            object_label,
+            environment_label,
+        }
+    }
+    pub fn randomized_objects_with_gap(
+        width: usize,
+        object_label: char,
+        environment_label: char,
+        density: f64,
+        min_gap_width: usize,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let mut row =
+            Self::randomized_objects(width, object_label, environment_label, density, rng);
+        Self::ensure_gap(&mut row.objects, min_gap_width, rng);
+        row
+    }
+    // Clears a random run of `min_gap_width` cells unless one is already empty
+    pub fn ensure_gap(objects: &mut [bool], min_gap_width: usize, rng: &mut impl Rng) {
+        let min_gap_width = min_gap_width.min(objects.len());
+        let mut run = 0;
+        for &obj in objects.iter() {
+            run = if obj { 0 } else { run + 1 };
+            if run >= min_gap_width {
+                return;
+            }
+        }
+        let start = rng.gen_range(0..=objects.len() - min_gap_width);
+        objects[start..start + min_gap_width].fill(false);
+    }
+    // Turns random empty cells into objects until at least `min` are present
+    pub fn fill_to_minimum(objects: &mut [bool], min: usize, rng: &mut impl Rng) {
+        let min = min.min(objects.len());
+        while objects.iter().filter(|&&obj| obj).count() < min {
+            let index = rng.gen_range(0..objects.len());
+            objects[index] = true;
+        }
+    }
+}
+
+// The row's labels in one line, for eprintln! debugging without a GameState
+impl fmt::Display for BaseRow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &obj in &self.objects {
+            let label = if obj {
+                self.object_label
+            } else {
+                self.environment_label
+            };
+            write!(f, "{}", label)?;
+        }
+        Ok(())
+    }
+}
+
+// Alternates between red (row frozen) and green (row shifts as normal)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficLight {
+    red_ticks: u8,
+    green_ticks: u8,
+    elapsed: u8,
+    red: bool,
+}
+
+impl TrafficLight {
+    pub fn new(red_ticks: u8, green_ticks: u8) -> Self {
+        Self {
+            red_ticks,
+            green_ticks,
+            elapsed: 0,
+            red: false,
+        }
+    }
+    pub fn tick(&mut self) {
+        self.elapsed = self.elapsed.saturating_add(1);
+        let phase_length = if self.red {
+            self.red_ticks
+        } else {
+            self.green_ticks
+        };
+        if self.elapsed >= phase_length {
+            self.elapsed = 0;
+            self.red = !self.red;
+        }
+    }
+    pub fn is_red(&self) -> bool {
+        self.red
+    }
+    pub fn glyph(&self) -> char {
+        if self.red {
+            RED_LIGHT
+        } else {
+            GREEN_LIGHT
+        }
+    }
+}
+
+// Custom per-tick behaviour for experimenting with new rows, e.g. random reversals or bursts
+#[derive(Clone)]
+pub struct TickHook(Arc<dyn Fn(&mut DynamicRow) + Send + Sync>);
+
+impl TickHook {
+    pub fn new(hook: impl Fn(&mut DynamicRow) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+
+    pub fn reverse_every(ticks: u64) -> Self {
+        Self::new(move |row| {
+            if ticks > 0 && row.age.is_multiple_of(ticks) {
+                row.reverse();
+            }
+        })
+    }
+}
+
+impl Debug for TickHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TickHook")
+    }
+}
+
+// How objects behave at the row's edges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrafficMode {
+    // objects leaving one edge come back in at the other
+    #[default]
+    Wrap,
+    // the row reverses instead of letting an object leave, so traffic oscillates
+    Bounce,
+}
+
+#[derive(Debug, Clone)]
+pub struct DynamicRow {
+    row: BaseRow,
+    direction: bool,
+    // the row shifts once every `interval` ticks
+    interval: u8,
+    // ticks since the last shift, always below interval
+    tick_count: u8,
+    light: Option<TrafficLight>,
+    // total ticks this row has seen
+    age: u64,
+    hook: Option<TickHook>,
+    // whether the last tick shifted the row
+    shifted: bool,
+    // how many times tick has shifted the row
+    shifts: u64,
+    mode: TrafficMode,
+    // identity of the object in each cell, carried along as the row shifts
+    ids: Vec<Option<u32>>,
+    next_id: u32,
+    // age at which the object in each cell came in over the edge; None for the starting ones
+    entered: Vec<Option<u64>>,
+}
+
+impl DynamicRow {
+    // phase offsets the starting tick_count so rows with the same interval don't shift in lockstep.
+    // Panics on an interval of 0, since tick_count has to stay below it; anything read from a
+    // file or config is checked before it gets here.
+    pub fn new(row: BaseRow, direction: bool, interval: u8, phase: u8) -> Self {
+        assert!(interval > 0, "a moving row's interval must be at least 1");
+        let mut next_id = 0;
+        let entered = vec![None; row.objects.len()];
+        let ids = row
+            .objects
+            .iter()
+            .map(|&obj| {
+                obj.then(|| {
+                    next_id += 1;
+                    next_id
+                })
+            })
+            .collect();
+        Self {
+            row,
+            direction,
+            interval,
+            tick_count: phase % interval,
+            light: None,
+            age: 0,
+            hook: None,
+            shifted: false,
+            shifts: 0,
+            mode: TrafficMode::default(),
+            ids,
+            next_id,
+            entered,
+        }
+    }
+    pub fn object_id(&self, column_index: usize) -> Option<u32> {
+        self.ids.get(column_index).copied().flatten()
+    }
+    // Frames since the object in this cell came in over the edge, while still under FADE_FRAMES
+    pub fn fade_stage(&self, column_index: usize) -> Option<usize> {
+        let entered = self.entered.get(column_index).copied().flatten()?;
+        let since = (self.age - entered) as usize;
+        (since < FADE_FRAMES).then_some(since)
+    }
+    // The edge cell an object will come in at on the next tick, if one is about to
+    pub fn incoming(&self) -> Option<usize> {
+        let last = self.row.objects.len().checked_sub(1)?;
+        if self.mode == TrafficMode::Bounce
+            || self.light.as_ref().is_some_and(|light| light.is_red())
+            || self.tick_count + 1 < self.interval
+        {
+            return None;
+        }
+        let (leaving, entering) = if self.direction { (last, 0) } else { (0, last) };
+        self.row.objects[leaving].then_some(entering)
+    }
+    pub fn with_tick_hook(mut self, hook: TickHook) -> Self {
+        self.hook = Some(hook);
+        self
+    }
+    pub fn with_traffic_mode(mut self, mode: TrafficMode) -> Self {
+        self.mode = mode;
+        self
+    }
+    pub fn shifted(&self) -> bool {
+        self.shifted
+    }
+    pub fn age(&self) -> u64 {
+        self.age
+    }
+    pub fn interval(&self) -> u8 {
+        self.interval
+    }
+    pub fn shifts(&self) -> u64 {
+        self.shifts
+    }
+    pub fn reverse(&mut self) {
+        self.direction = !self.direction;
+    }
+    // The contract: tick_count counts up by one per tick and the row shifts on the tick that
+    // brings it to interval, so with phase 0 the first shift is on tick N and after N*K ticks
+    // there have been exactly K. A phase of P moves each shift P ticks earlier. Ticks on a red
+    // light don't count towards the next shift. The hook runs first so it can change direction
+    // or speed before this tick's shift.
+    pub fn tick(&mut self) {
+        self.shifted = false;
+        self.age += 1;
+        if let Some(hook) = self.hook.clone() {
+            (hook.0)(self);
+        }
+        if let Some(light) = &mut self.light {
+            light.tick();
+            if light.is_red() {
+                return;
+            }
+        }
+        self.tick_count = self.tick_count.saturating_add(1);
+        if self.tick_count >= self.interval {
+            self.tick_count = 0;
+            self.update_row();
+            self.shifted = true;
+            self.shifts += 1;
+        }
+    }
+    pub fn update_row(&mut self) {
+        if self.row.objects.is_empty() {
+            return;
+        }
+        if self.mode == TrafficMode::Bounce {
+            // only an empty cell ever wraps around, so no object leaves the row
+            let leading_edge = |direction: bool, objects: &[bool]| {
+                if direction {
+                    objects[objects.len() - 1]
+                } else {
+                    objects[0]
+                }
+            };
+            if leading_edge(self.direction, &self.row.objects) {
+                self.reverse();
+                if leading_edge(self.direction, &self.row.objects) {
+                    return;
+                }
+            }
+        }
+        let entering = if self.direction {
+            self.row.objects.rotate_right(1);
+            self.ids.rotate_right(1);
+            self.entered.rotate_right(1);
+            0
+        } else {
+            self.row.objects.rotate_left(1);
+            self.ids.rotate_left(1);
+            self.entered.rotate_left(1);
+            self.ids.len() - 1
+        };
+        // an object wrapping round has left the row; what comes in at the far edge is a new one
+        if self.ids[entering].is_some() {
+            self.next_id += 1;
+            self.ids[entering] = Some(self.next_id);
+            self.entered[entering] = Some(self.age);
+        }
+    }
+}
+
+impl fmt::Display for DynamicRow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.row)
+    }
+}
+
+// Whether a frog waiting on the bank in one of the entry columns can get across these moving
+// rows (bottom row first) within max_ticks. Each tick the rows move, carrying a frog on a pad
+// along with it, and then the frog either waits or hops one cell, which is how GameState::tick
+// orders things. A pad that wraps round the edge takes its frog off the board.
+pub fn river_crossable(rows: &[DynamicRow], entry: &[usize], max_ticks: u64) -> bool {
+    let mut rows = rows.to_vec();
+    let far_bank = rows.len() + 1;
+    let width = rows.first().map_or(0, |row| row.row.objects.len());
+    let drifted = |rows: &[DynamicRow], level: usize, column: usize| {
+        let Some(row) = level
+            .checked_sub(1)
+            .and_then(|index| rows.get(index))
+            .filter(|row| row.shifted())
+        else {
+            return Some(column);
+        };
+        let target = if row.direction {
+            column + 1
+        } else {
+            column.wrapping_sub(1)
+        };
+        (target < width).then_some(target)
+    };
+    let safe = |rows: &[DynamicRow], level: usize, column: usize| match level {
+        0 => entry.contains(&column),
+        level if level == far_bank => true,
+        level => rows[level - 1].row.objects.get(column) == Some(&true),
+    };
+    let mut reachable: HashSet<(usize, usize)> = entry.iter().map(|&column| (0, column)).collect();
+    for _ in 0..max_ticks {
+        for row in &mut rows {
+            row.tick();
+        }
+        let mut next = HashSet::new();
+        for &(level, column) in &reachable {
+            let Some(column) = drifted(&rows, level, column) else {
+                continue;
+            };
+            let moves = [
+                (level, column),
+                (level + 1, column),
+                (level.saturating_sub(1), column),
+                (level, column.saturating_sub(1)),
+                (level, column + 1),
+            ];
+            for (level, column) in moves {
+                if level == far_bank {
+                    return true;
+                }
+                if safe(&rows, level, column) {
+                    next.insert((level, column));
+                }
+            }
+        }
+        if next.is_empty() {
+            return false;
+        }
+        reachable = next;
+    }
+    false
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RowKind {
+    Grass,
+    Road,
+    Stream,
+    Ice,
+    Train,
+}
+
+impl RowKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            RowKind::Grass => "grass",
+            RowKind::Road => "road",
+            RowKind::Stream => "stream",
+            RowKind::Ice => "ice",
+            RowKind::Train => "train",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RowKind> {
+        match name {
+            "grass" => Some(RowKind::Grass),
+            "road" => Some(RowKind::Road),
+            "stream" => Some(RowKind::Stream),
+            "ice" => Some(RowKind::Ice),
+            "train" => Some(RowKind::Train),
+            _ => None,
+        }
+    }
+}
+
+pub trait RowType: Debug {
+    fn kind(&self) -> RowKind;
+    fn get_base_row(&self) -> &BaseRow;
+    // Only rows that move have one
+    fn dynamic_row(&self) -> Option<&DynamicRow> {
+        None
+    }
+    fn tick(&mut self) -> Option<bool>;
+    fn check_position(&self, column_index: usize) -> Option<bool>;
+    fn cell(&self, column_index: usize) -> Option<Cell>;
+    fn clone_row(&self) -> Box<dyn RowType>;
+    // Stable identity of the moving object in a cell, so one car can be followed as it shifts
+    fn object_id(&self, column_index: usize) -> Option<u32> {
+        self.dynamic_row()
+            .and_then(|row| row.object_id(column_index))
+    }
+    // Extra glyph drawn at the end of the row, e.g. a traffic light
+    fn edge_glyph(&self) -> Option<char> {
+        None
+    }
+    // What to draw for the object in a cell, for rows whose objects don't all look alike
+    fn object_label(&self, _column_index: usize) -> char {
+        self.get_base_row().object_label
+    }
+    // Only grass carries pickups; everything else reads as empty
+    fn content(&self, _column_index: usize) -> CellContent {
+        CellContent::Empty
+    }
+    fn take_pickup(&mut self, _column_index: usize) -> Option<CellContent> {
+        None
+    }
+    fn assert_invariants(&self) {}
+    fn is_lethal_at(&self, column_index: usize) -> bool {
+        matches!(self.cell(column_index), Some(Cell::Lethal(_)))
+    }
+    // Whether a frog standing on this row moves along with it
+    fn carries_player(&self) -> bool {
+        false
+    }
+    // Whether a stream's pads move; see Stream::flowing
+    fn is_flowing(&self) -> bool {
+        false
+    }
+    // Cells of a river that hold a rock
+    fn rocks(&self) -> Option<&[bool]> {
+        None
+    }
+    // Rough measure of how hard the row is to cross, 0 for a free pass
+    fn difficulty_rating(&self) -> u8 {
+        0
+    }
+    // Puts a power-up on the row if it can hold one and doesn't already
+    fn place_power_up(&mut self, _rng: &mut dyn RngCore) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Stream {
+    pub dynamic_row: DynamicRow,
+    // Generated streams flow, moving their pads like traffic and carrying the frog along;
+    // a still stream keeps its pads in place
+    pub flowing: bool,
+}
+
+impl Stream {
+    pub fn new(objects: Vec<bool>, interval: u8, direction: bool, phase: u8) -> Self {
+        Self {
+            dynamic_row: DynamicRow::new(
+                BaseRow::new(objects, PAD, WATER),
+                direction,
+                interval,
+                phase,
+            ),
+            flowing: false,
+        }
+    }
+
+    pub fn flowing(mut self) -> Self {
+        self.flowing = true;
+        self
+    }
+
+    // Two flowing streams in opposite directions with only a couple of pads each, so crossing
+    // means waiting for the pads to line up. Layouts that can't be crossed within
+    // max_ticks are rerolled, falling back to pads stacked in the same column.
+    pub fn two_step_river(width: usize, rng: &mut impl Rng, max_ticks: u64) -> [Stream; 2] {
+        let entry: Vec<usize> = (0..width).collect();
+        let roll = |direction: bool, mut rng: &mut dyn RngCore| {
+            let mut objects = vec![false; width];
+            let pads = rng.gen_range(1..=2);
+            BaseRow::fill_to_minimum(&mut objects, pads, &mut rng);
+            let interval = rng.gen_range(2..=4);
+            let phase = rng.gen_range(0..interval);
+            Stream::new(objects, interval, direction, phase).flowing()
+        };
+        for _ in 0..MAX_ROW_RETRIES {
+            let direction = rng.gen_bool(0.5);
+            let river = [roll(direction, rng), roll(!direction, rng)];
+            let rows = [river[0].dynamic_row.clone(), river[1].dynamic_row.clone()];
+            if river_crossable(&rows, &entry, max_ticks) {
+                return river;
+            }
+        }
+        let mut objects = vec![false; width];
+        objects[width / 2] = true;
+        [
+            Stream::new(objects.clone(), 1, true, 0),
+            Stream::new(objects, 1, true, 0),
+        ]
+    }
+}
+
+impl RowType for Stream {
+    fn cell(&self, column_index: usize) -> Option<Cell> {
+        self.check_position(column_index).map(|pad| {
+            if pad {
+                Cell::Passable
+            } else {
+                Cell::Lethal(CauseOfDeath::Drowned)
+            }
+        })
+    }
+    fn kind(&self) -> RowKind {
+        RowKind::Stream
+    }
+    // fewer pads is harder
+    fn difficulty_rating(&self) -> u8 {
+        let pads = self.dynamic_row.row.object_count();
+        14u8.saturating_sub((pads * 3).min(u8::MAX as usize) as u8)
+    }
+    fn get_base_row(&self) -> &BaseRow {
+        &self.dynamic_row.row
+    }
+    fn dynamic_row(&self) -> Option<&DynamicRow> {
+        Some(&self.dynamic_row)
+    }
+    fn tick(&mut self) -> Option<bool> {
+        if self.flowing {
+            self.dynamic_row.tick();
+        }
+        Some(self.dynamic_row.direction)
+    }
+    fn carries_player(&self) -> bool {
+        true
+    }
+    fn is_flowing(&self) -> bool {
+        self.flowing
+    }
+    fn check_position(&self, column_index: usize) -> Option<bool> {
+        self.dynamic_row.row.objects.get(column_index).copied()
+    }
+    fn clone_row(&self) -> Box<dyn RowType> {
+        Box::new(self.clone())
+    }
+}
+
+// A flowing stream with rocks in it. The rocks stay where they were generated while the pads
+// drift past them, and the frog can't hop onto one. A pad carrying the frog into a rock leaves
+// it standing on the rock until it hops off.
+#[derive(Debug, Clone)]
+pub struct River {
+    pub stream: Stream,
+    pub rocks: Vec<bool>,
+}
+
+impl River {
+    // Rocks only go where there is no pad to start with
+    pub fn new(
+        objects: Vec<bool>,
+        interval: u8,
+        direction: bool,
+        phase: u8,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let rocks = objects
+            .iter()
+            .map(|&pad| !pad && rng.gen_bool(ROCK_CHANCE))
+            .collect();
+        Self {
+            stream: Stream::new(objects, interval, direction, phase).flowing(),
+            rocks,
+        }
+    }
+
+    fn is_rock(&self, column_index: usize) -> bool {
+        self.rocks.get(column_index) == Some(&true)
+    }
+}
+
+impl RowType for River {
+    fn cell(&self, column_index: usize) -> Option<Cell> {
+        if self.is_rock(column_index) {
+            return Some(Cell::Blocking);
+        }
+        self.stream.cell(column_index)
+    }
+    fn kind(&self) -> RowKind {
+        RowKind::Stream
+    }
+    fn difficulty_rating(&self) -> u8 {
+        self.stream.difficulty_rating()
+    }
+    fn get_base_row(&self) -> &BaseRow {
+        self.stream.get_base_row()
+    }
+    fn dynamic_row(&self) -> Option<&DynamicRow> {
+        self.stream.dynamic_row()
+    }
+    fn tick(&mut self) -> Option<bool> {
+        self.stream.tick()
+    }
+    fn carries_player(&self) -> bool {
+        true
+    }
+    fn is_flowing(&self) -> bool {
+        self.stream.flowing
+    }
+    fn rocks(&self) -> Option<&[bool]> {
+        Some(&self.rocks)
+    }
+    fn check_position(&self, column_index: usize) -> Option<bool> {
+        self.stream.check_position(column_index)
+    }
+    fn clone_row(&self) -> Box<dyn RowType> {
+        Box::new(self.clone())
+    }
+    fn content(&self, column_index: usize) -> CellContent {
+        if self.is_rock(column_index) {
+            CellContent::Rock
+        } else {
+            CellContent::Empty
+        }
+    }
+}
+
+// The columns of each run of occupied cells, a run that wraps round the edge counting as one
+fn vehicle_cells(objects: &[bool]) -> Vec<Vec<usize>> {
+    let width = objects.len();
+    let Some(gap) = objects.iter().position(|&obj| !obj) else {
+        return if width == 0 {
+            Vec::new()
+        } else {
+            vec![(0..width).collect()]
+        };
+    };
+    let mut vehicles = Vec::new();
+    let mut vehicle = Vec::new();
+    // starting after a gap and ending on it, so no run is split or left open
+    for column in (gap + 1..=gap + width).map(|column| column % width) {
+        if objects[column] {
+            vehicle.push(column);
+        } else if !vehicle.is_empty() {
+            vehicles.push(std::mem::take(&mut vehicle));
+        }
+    }
+    vehicles
+}
+
+#[derive(Debug, Clone)]
+pub struct Road {
+    pub dynamic_row: DynamicRow,
+    // What each cell's vehicle looks like, None where the road is empty. Collisions still go
+    // by the row's objects.
+    pub vehicle_types: Vec<Option<char>>,
+    // picks the look of each vehicle coming in over the edge; without one they are all cars
+    vehicle_rng: Option<StdRng>,
+}
+
+impl Road {
+    pub fn new(objects: Vec<bool>, interval: u8, direction: bool, phase: u8) -> Self {
+        let vehicle_types = objects.iter().map(|&car| car.then_some(CAR)).collect();
+        Self {
+            dynamic_row: DynamicRow::new(
+                BaseRow::new(objects, CAR, ROAD),
+                direction,
+                interval,
+                phase,
+            ),
+            vehicle_types,
+            vehicle_rng: None,
+        }
+    }
+    // Cars, taxis, SUVs and buses instead of only cars. A vehicle several cells long is drawn
+    // as one of them throughout.
+    pub fn with_mixed_vehicles(mut self, rng: &mut impl Rng) -> Self {
+        self.vehicle_rng = Some(StdRng::seed_from_u64(rng.gen()));
+        for vehicle in vehicle_cells(&self.dynamic_row.row.objects) {
+            let look = self.next_vehicle();
+            for column in vehicle {
+                self.vehicle_types[column] = Some(look);
+            }
+        }
+        self
+    }
+    fn next_vehicle(&mut self) -> char {
+        match &mut self.vehicle_rng {
+            Some(rng) => VEHICLES[rng.gen_range(0..VEHICLES.len())],
+            None => CAR,
+        }
+    }
+    // Vehicles keep their look as they shift by following their object ids. A cell coming in
+    // over the edge takes the look of the vehicle it joins, and only a vehicle with no cell
+    // on the row before is new.
+    fn update_vehicle_types(&mut self, before: &[(u32, char)]) {
+        let mut vehicle_types = vec![None; self.vehicle_types.len()];
+        for vehicle in vehicle_cells(&self.dynamic_row.row.objects) {
+            let known = vehicle.iter().find_map(|&column| {
+                let id = self.dynamic_row.object_id(column)?;
+                before
+                    .iter()
+                    .find(|&&(old, _)| old == id)
+                    .map(|&(_, look)| look)
+            });
+            let look = known.unwrap_or_else(|| self.next_vehicle());
+            for column in vehicle {
+                vehicle_types[column] = Some(look);
+            }
+        }
+        self.vehicle_types = vehicle_types;
+    }
+    pub fn with_tick_hook(mut self, hook: TickHook) -> Self {
+        self.dynamic_row = self.dynamic_row.with_tick_hook(hook);
+        self
+    }
+    pub fn with_traffic_mode(mut self, mode: TrafficMode) -> Self {
+        self.dynamic_row = self.dynamic_row.with_traffic_mode(mode);
+        self
+    }
+    pub fn with_traffic_light(mut self, red_ticks: u8, green_ticks: u8) -> Self {
+        self.dynamic_row.light = Some(TrafficLight::new(red_ticks, green_ticks));
+        self
+    }
+}
+
+impl RowType for Road {
+    fn cell(&self, column_index: usize) -> Option<Cell> {
+        self.check_position(column_index).map(|car| {
+            if car {
+                Cell::Lethal(CauseOfDeath::HitByCar)
+            } else {
+                Cell::Passable
+            }
+        })
+    }
+    fn kind(&self) -> RowKind {
+        RowKind::Road
+    }
+    fn difficulty_rating(&self) -> u8 {
+        let cars = self.dynamic_row.row.object_count();
+        (cars * 2).min(u8::MAX as usize) as u8
+    }
+    fn get_base_row(&self) -> &BaseRow {
+        &self.dynamic_row.row
+    }
+    fn dynamic_row(&self) -> Option<&DynamicRow> {
+        Some(&self.dynamic_row)
+    }
+    fn tick(&mut self) -> Option<bool> {
+        let before: Vec<(u32, char)> = (0..self.vehicle_types.len())
+            .filter_map(|column| {
+                Some((
+                    self.dynamic_row.object_id(column)?,
+                    self.vehicle_types[column]?,
+                ))
+            })
+            .collect();
+        self.dynamic_row.tick();
+        if self.dynamic_row.shifted() {
+            self.update_vehicle_types(&before);
+        }
+        None
+    }
+    fn check_position(&self, column_index: usize) -> Option<bool> {
+        self.dynamic_row.row.objects.get(column_index).copied()
+    }
+    fn clone_row(&self) -> Box<dyn RowType> {
+        Box::new(self.clone())
+    }
+    fn edge_glyph(&self) -> Option<char> {
+        self.dynamic_row.light.as_ref().map(|light| light.glyph())
+    }
+    fn object_label(&self, column_index: usize) -> char {
+        self.vehicle_types
+            .get(column_index)
+            .copied()
+            .flatten()
+            .unwrap_or(CAR)
+    }
+}
+
+// Railway track: empty for dormant_ticks, then the train fills every column for
+// TRAIN_ACTIVE_TICKS before the track clears again. There is no gap to dodge into.
+#[derive(Debug, Clone)]
+pub struct TrainRow {
+    pub baserow: BaseRow,
+    pub dormant_ticks: u32,
+    // ticks left before the next train arrives
+    countdown: u32,
+    // ticks left on the current pass, zero while dormant
+    active: u8,
+}
+
+impl TrainRow {
+    pub fn new(width: usize, dormant_ticks: u32) -> Self {
+        Self {
+            baserow: BaseRow::new(vec![false; width], TRAIN, ROAD),
+            dormant_ticks,
+            countdown: dormant_ticks,
+            active: 0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active > 0
+    }
+
+    fn fill(&mut self, train: bool) {
+        self.baserow.objects.iter_mut().for_each(|obj| *obj = train);
+    }
+}
+
+// Trains come round more often the higher the score
+pub fn train_dormant_ticks(score: u32) -> u32 {
+    60u32.saturating_sub(score / 4).max(15)
+}
+
+impl RowType for TrainRow {
+    fn cell(&self, column_index: usize) -> Option<Cell> {
+        self.check_position(column_index).map(|train| {
+            if train {
+                Cell::Lethal(CauseOfDeath::HitByCar)
+            } else {
+                Cell::Passable
+            }
+        })
+    }
+    fn kind(&self) -> RowKind {
+        RowKind::Train
+    }
+    // nowhere to dodge to, as bad as a stream without pads
+    fn difficulty_rating(&self) -> u8 {
+        14
+    }
+    fn get_base_row(&self) -> &BaseRow {
+        &self.baserow
+    }
+    fn tick(&mut self) -> Option<bool> {
+        if self.active > 0 {
+            self.active -= 1;
+            if self.active == 0 {
+                // the tick the track clears is the first of the dormant ones
+                self.fill(false);
+                self.countdown = self.dormant_ticks.saturating_sub(1);
+            }
+        } else if self.countdown == 0 {
+            self.fill(true);
+            self.active = TRAIN_ACTIVE_TICKS;
+        } else {
+            self.countdown -= 1;
+        }
+        None
+    }
+    fn check_position(&self, column_index: usize) -> Option<bool> {
+        self.baserow.objects.get(column_index).copied()
+    }
+    fn clone_row(&self) -> Box<dyn RowType> {
+        Box::new(self.clone())
+    }
+}
+
+// contents is the source of truth for what sits on each cell; baserow.objects mirrors its
+// obstacles so the rest of the board code can treat grass like any other row
+#[derive(Debug, Clone)]
+pub struct Grass {
+    pub baserow: BaseRow,
+    pub contents: Vec<CellContent>,
+}
+
+impl Grass {
+    pub fn new(objects: Vec<bool>) -> Self {
+        let contents = objects
+            .iter()
+            .map(|&tree| {
+                if tree {
+                    CellContent::Obstacle
+                } else {
+                    CellContent::Empty
+                }
+            })
+            .collect();
+        Self::from_contents(contents)
+    }
+
+    pub fn from_contents(contents: Vec<CellContent>) -> Self {
+        let objects = contents
+            .iter()
+            .map(|&content| content == CellContent::Obstacle)
+            .collect();
+        Self {
+            baserow: BaseRow::new(objects, TREE, GRASS),
+            contents,
+        }
+    }
+
+    // Coins only go on cells the trees left empty, so no cell ever holds two things
+    pub fn with_coins(objects: Vec<bool>, chance: f64, rng: &mut impl Rng) -> Self {
+        let contents = objects
+            .iter()
+            .map(|&tree| {
+                if tree {
+                    CellContent::Obstacle
+                } else if rng.gen_bool(chance) {
+                    CellContent::Coin
+                } else {
+                    CellContent::Empty
+                }
+            })
+            .collect();
+        Self::from_contents(contents)
+    }
+
+    // At most one power-up per row, with `chance` of there being one. Like coins, power-ups
+    // only replace empty cells.
+    pub fn with_power_ups(mut self, chance: f64, rng: &mut impl Rng) -> Self {
+        if rng.gen_bool(chance) {
+            self.add_power_up(rng);
+        }
+        self
+    }
+
+    fn add_power_up(&mut self, rng: &mut impl Rng) -> bool {
+        if self.powerup().is_some() {
+            return true;
+        }
+        let empty: Vec<usize> = (0..self.contents.len())
+            .filter(|&column| self.contents[column] == CellContent::Empty)
+            .collect();
+        if empty.is_empty() {
+            return false;
+        }
+        let column = empty[rng.gen_range(0..empty.len())];
+        self.contents[column] = CellContent::PowerUp(PowerUpKind::random(rng));
+        true
+    }
+
+    pub fn powerup(&self) -> Option<(usize, PowerUpKind)> {
+        self.contents
+            .iter()
+            .enumerate()
+            .find_map(|(column, content)| match content {
+                CellContent::PowerUp(kind) => Some((column, *kind)),
+                _ => None,
+            })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.contents.len() != self.baserow.objects.len() {
+            return Err(format!(
+                "grass has {} contents for {} cells",
+                self.contents.len(),
+                self.baserow.objects.len()
+            ));
+        }
+        let cells = self.contents.iter().zip(&self.baserow.objects);
+        for (column, (&content, &tree)) in cells.enumerate() {
+            if (content == CellContent::Obstacle) != tree {
+                return Err(format!(
+                    "grass column {} holds {:?} but its tree flag is {}",
+                    column, content, tree
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RowType for Grass {
+    fn cell(&self, column_index: usize) -> Option<Cell> {
+        self.check_position(column_index).map(
+            |tree| {
+                if tree {
+                    Cell::Blocking
+                } else {
+                    Cell::Passable
+                }
+            },
+        )
+    }
+    fn kind(&self) -> RowKind {
+        RowKind::Grass
+    }
+    fn place_power_up(&mut self, mut rng: &mut dyn RngCore) -> bool {
+        self.add_power_up(&mut rng)
+    }
+    fn content(&self, column_index: usize) -> CellContent {
+        self.contents
+            .get(column_index)
+            .copied()
+            .unwrap_or(CellContent::Empty)
+    }
+    fn take_pickup(&mut self, column_index: usize) -> Option<CellContent> {
+        let content = self.contents.get_mut(column_index)?;
+        match *content {
+            CellContent::Coin | CellContent::PowerUp(_) => {
+                Some(std::mem::replace(content, CellContent::Empty))
+            }
+            _ => None,
+        }
+    }
+    fn assert_invariants(&self) {
+        if let Err(message) = self.validate() {
+            panic!("{}", message);
+        }
+    }
+    fn get_base_row(&self) -> &BaseRow {
+        &self.baserow
+    }
+    fn tick(&mut self) -> Option<bool> {
+        None
+    }
+    fn check_position(&self, column_index: usize) -> Option<bool> {
+        self.baserow.objects.get(column_index).copied()
+    }
+    fn clone_row(&self) -> Box<dyn RowType> {
+        Box::new(self.clone())
+    }
+}
+
+// Frozen grass: trees still block, but the frog keeps sliding sideways until stopped
+#[derive(Debug, Clone)]
+pub struct Ice {
+    pub baserow: BaseRow,
+}
+
+impl Ice {
+    pub fn new(objects: Vec<bool>) -> Self {
+        Self {
+            baserow: BaseRow::new(objects, TREE, ICE),
+        }
+    }
+}
+
+impl RowType for Ice {
+    fn cell(&self, column_index: usize) -> Option<Cell> {
+        self.check_position(column_index).map(
+            |tree| {
+                if tree {
+                    Cell::Blocking
+                } else {
+                    Cell::Passable
+                }
+            },
+        )
+    }
+    fn kind(&self) -> RowKind {
+        RowKind::Ice
+    }
+    fn get_base_row(&self) -> &BaseRow {
+        &self.baserow
+    }
+    fn tick(&mut self) -> Option<bool> {
+        None
+    }
+    fn check_position(&self, column_index: usize) -> Option<bool> {
+        self.baserow.objects.get(column_index).copied()
+    }
+    fn clone_row(&self) -> Box<dyn RowType> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelConfig {
+    // rows between the starting grass and the finish line
+    pub length: usize,
+    // total cars, pads and trees spread across the whole level
+    pub object_budget: usize,
+}
+
+// A finite, pre-generated run of rows; the board scrolls through it and then hits the finish line
+#[derive(Debug)]
+pub struct Level {
+    config: LevelConfig,
+    rows: VecDeque<Box<dyn RowType>>,
+    width: usize,
+}
+
+impl Level {
+    // Holds no rows until reset generates them, which new_game does at the start of each run
+    pub fn new(config: LevelConfig, width: usize) -> Self {
+        Self {
+            config,
+            rows: VecDeque::new(),
+            width,
+        }
+    }
+
+    // Every road and stream gets one object first so none is a free pass or impassable, then the
+    // rest of the budget lands on random cells. Rows always keep one open cell, so the level may
+    // hold fewer objects than the budget but never more.
+    pub fn generate(config: LevelConfig, width: usize, rng: &mut impl Rng) -> Self {
+        let mut kinds = Vec::with_capacity(config.length);
+        let mut history = RowHistory::default();
+        for _ in 0..config.length {
+            let kind = choose_row_kind(history, rng);
+            history.push(kind);
+            kinds.push(kind);
+        }
+        let mut objects = vec![vec![false; width]; config.length];
+        let mut budget = config.object_budget;
+        // every row keeps an open cell, so a single-cell board gets no objects at all
+        for (row, kind) in objects.iter_mut().zip(&kinds) {
+            if budget > 0 && width > 1 && *kind != RowKind::Grass {
+                row[rng.gen_range(0..width)] = true;
+                budget -= 1;
+            }
+        }
+        while budget > 0 {
+            let open: Vec<(usize, usize)> = objects
+                .iter()
+                .enumerate()
+                .filter(|(_, row)| row.iter().filter(|&&obj| obj).count() + 1 < width)
+                .flat_map(|(index, row)| {
+                    row.iter()
+                        .enumerate()
+                        .filter(|(_, &obj)| !obj)
+                        .map(move |(column, _)| (index, column))
+                })
+                .collect();
+            if open.is_empty() {
+                break;
+            }
+            let (index, column) = open[rng.gen_range(0..open.len())];
+            objects[index][column] = true;
+            budget -= 1;
+        }
+        let rows = kinds
+            .into_iter()
+            .zip(objects)
+            .map(|(kind, objects)| -> Box<dyn RowType> {
+                let interval = rng.gen_range(1..=5);
+                let direction = rng.gen_bool(0.5);
+                let phase = rng.gen_range(0..interval);
+                match kind {
+                    RowKind::Stream => {
+                        Box::new(Stream::new(objects, interval, direction, phase).flowing())
+                    }
+                    RowKind::Road => Box::new(Road::new(objects, interval, direction, phase)),
+                    RowKind::Ice => Box::new(Ice::new(objects)),
+                    RowKind::Grass => Box::new(Grass::new(objects)),
+                    RowKind::Train => Box::new(TrainRow::new(width, train_dormant_ticks(0))),
+                }
+            })
+            .collect();
+        Self {
+            config,
+            rows,
+            width,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn object_count(&self) -> usize {
+        self.rows
+            .iter()
+            .map(|row| {
+                row.get_base_row()
+                    .objects
+                    .iter()
+                    .filter(|&&obj| obj)
+                    .count()
+            })
+            .sum()
+    }
+
+    // Once the level runs out every further row is open grass: the finish line
+    pub fn next_row(&mut self) -> Box<dyn RowType> {
+        self.rows
+            .pop_front()
+            .unwrap_or_else(|| Box::new(Grass::new(vec![false; self.width])))
+    }
+}
+
+// Decides which row comes next as the board scrolls. history lists the kinds currently on the
+// board, bottom row first, so the last entry is the row the new one will sit above.
+pub trait SpawnStrategy {
+    fn next_row(&mut self, history: &[RowKind], rng: &mut dyn RngCore) -> Box<dyn RowType>;
+    // Told the current score before each row is asked for, for strategies that ramp up
+    fn set_score(&mut self, _score: u32) {}
+    // Called by new_game with the new run's rng before it asks for the run's first row, so the
+    // strategy can start over
+    fn reset(&mut self, _rng: &mut dyn RngCore) {}
+    // A copy as it is now, which undo puts back. Strategies without one carry on from wherever
+    // they are after an undo.
+    fn clone_strategy(&self) -> Option<Box<dyn SpawnStrategy>> {
+        None
+    }
+}
+
+// The endless game's generator
+#[derive(Debug)]
+pub struct RandomSpawn {
+    config: GameConfig,
+    // rows of a multi-row feature still waiting to be placed
+    pending: VecDeque<Box<dyn RowType>>,
+    score: u32,
+}
+
+impl RandomSpawn {
+    pub fn new(config: GameConfig) -> Self {
+        Self {
+            config,
+            pending: VecDeque::new(),
+            score: 0,
+        }
+    }
+}
+
+impl SpawnStrategy for RandomSpawn {
+    fn next_row(&mut self, history: &[RowKind], mut rng: &mut dyn RngCore) -> Box<dyn RowType> {
+        if let Some(row) = self.pending.pop_front() {
+            return row;
+        }
+        let history = RowHistory::of(history);
+        let row = GameState::create_random_row(&self.config, history, &mut rng);
+        if row.kind() == RowKind::Stream && self.config.two_step_rivers && rng.gen_bool(0.3) {
+            let width = self.config.board.width;
+            let [near, far] = Stream::two_step_river(width, &mut rng, TWO_STEP_RIVER_TICKS);
+            self.pending.push_back(Box::new(far));
+            return Box::new(near);
+        }
+        if row.kind() == RowKind::Road && self.score > TRAIN_MIN_SCORE && rng.gen_bool(TRAIN_CHANCE)
+        {
+            let width = self.config.board.width;
+            return Box::new(TrainRow::new(width, train_dormant_ticks(self.score)));
+        }
+        row
+    }
+
+    fn set_score(&mut self, score: u32) {
+        self.score = score;
+    }
+
+    fn reset(&mut self, _rng: &mut dyn RngCore) {
+        self.pending.clear();
+        self.score = 0;
+    }
+
+    fn clone_strategy(&self) -> Option<Box<dyn SpawnStrategy>> {
+        Some(Box::new(RandomSpawn {
+            config: self.config.clone(),
+            pending: self.pending.iter().map(|row| row.clone_row()).collect(),
+            score: self.score,
+        }))
+    }
+}
+
+// A fixed loop of row kinds for the wrap-around world. Passing the top of the pool brings the
+// frog back to its first row with every row's contents rolled again. The loop itself is rolled
+// on reset, or by the first row asked for if there hasn't been one.
+#[derive(Debug, Clone)]
+pub struct WrapAroundSpawn {
+    config: GameConfig,
+    pool_size: usize,
+    kinds: Vec<RowKind>,
+    cursor: usize,
+    laps: u32,
+}
+
+impl WrapAroundSpawn {
+    pub fn new(config: GameConfig, pool_size: usize) -> Self {
+        Self {
+            config,
+            pool_size: pool_size.max(1),
+            kinds: Vec::new(),
+            cursor: 0,
+            laps: 0,
+        }
+    }
+
+    pub fn laps(&self) -> u32 {
+        self.laps
+    }
+}
+
+impl SpawnStrategy for WrapAroundSpawn {
+    fn next_row(&mut self, _history: &[RowKind], mut rng: &mut dyn RngCore) -> Box<dyn RowType> {
+        if self.kinds.is_empty() {
+            self.reset(rng);
+        }
+        let kind = self.kinds[self.cursor];
+        self.cursor = (self.cursor + 1) % self.kinds.len();
+        if self.cursor == 0 {
+            self.laps += 1;
+        }
+        GameState::create_row_of_kind(&self.config, kind, &mut rng)
+    }
+
+    fn reset(&mut self, mut rng: &mut dyn RngCore) {
+        self.kinds.clear();
+        let mut history = RowHistory::default();
+        for _ in 0..self.pool_size {
+            let kind = choose_row_kind(history, &mut rng);
+            history.push(kind);
+            self.kinds.push(kind);
+        }
+        self.cursor = 0;
+        self.laps = 0;
+    }
+
+    fn clone_strategy(&self) -> Option<Box<dyn SpawnStrategy>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+impl SpawnStrategy for Level {
+    fn next_row(&mut self, _history: &[RowKind], _rng: &mut dyn RngCore) -> Box<dyn RowType> {
+        Level::next_row(self)
+    }
+
+    fn reset(&mut self, mut rng: &mut dyn RngCore) {
+        *self = Level::generate(self.config, self.width, &mut rng);
+    }
+
+    fn clone_strategy(&self) -> Option<Box<dyn SpawnStrategy>> {
+        Some(Box::new(Level {
+            config: self.config,
+            rows: self.rows.iter().map(|row| row.clone_row()).collect(),
+            width: self.width,
+        }))
+    }
+}
+
+// In-memory copy of everything needed to put the player back at a practice spot
+#[derive(Debug)]
+pub struct Checkpoint {
+    gameboard: Vec<Box<dyn RowType>>,
+    player: (usize, usize),
+    player_score: u32,
+}
+
+impl Clone for Checkpoint {
+    fn clone(&self) -> Self {
+        Self {
+            gameboard: self.gameboard.iter().map(|row| row.clone_row()).collect(),
+            player: self.player,
+            player_score: self.player_score,
+        }
+    }
+}
+
+// Everything a turn can change, for undo in turn-based mode: the board, every per-run counter,
+// and the rng and spawner so the rows that come after an undo are the ones that came before it.
+// Quick save slots, replays and recordings are left alone, since they log what was played.
+pub struct TurnSnapshot {
+    rng: StdRng,
+    gameboard: Vec<Box<dyn RowType>>,
+    spawner: Option<Box<dyn SpawnStrategy>>,
+    player: (usize, usize),
+    player_score: u32,
+    game_over: bool,
+    collision_flash: u8,
+    collision_at: Option<(usize, usize)>,
+    step_count: u64,
+    rows_traversed: u32,
+    camera_x: usize,
+    grace_ticks: u32,
+    lives: u8,
+    invincibility_ticks: u8,
+    cause_of_death: Option<CauseOfDeath>,
+    elapsed: Duration,
+    slide: Option<Direction>,
+    last_horizontal_direction: Option<Direction>,
+    feedback: Option<&'static str>,
+    scrolled_rows: u64,
+    won: bool,
+    effects: Vec<ActiveEffect>,
+    npcs: Vec<NpcFrog>,
+    combo: u32,
+    combo_timeout: u8,
+    stats: SessionStats,
+    tick_interval: Duration,
+    time_remaining: Option<Duration>,
+    row_history: RowHistory,
+    previous_player_pos: Option<(usize, usize)>,
+    previous_move_points: u32,
+    hint: HintPath,
+    hint_ticks: u32,
+    power_up_owed: bool,
+    consecutive_hard_rows: u8,
+    safe_row_owed: bool,
+    water_drift_grace: u8,
+    wind_direction: i8,
+    wind_timer: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameConfig {
+    // The world only advances when the player presses a key
+    pub turn_based: bool,
+    // Roads are generated with at least this many cars so none is a free pass
+    pub min_cars_per_road: usize,
+    // Alternate the frog with whatever hit it for a few frames so the death is visible
+    pub collision_flash: bool,
+    // Each direction can be switched off for novelty or accessibility modes
+    pub allow_up: bool,
+    pub allow_down: bool,
+    pub allow_left: bool,
+    pub allow_right: bool,
+    // Only draw this many columns, scrolling sideways to keep the frog in view
+    pub viewport_width: Option<usize>,
+    // Columns the frog can get from either viewport edge before the camera scrolls
+    pub camera_deadzone: usize,
+    pub high_score_policy: HighScorePolicy,
+    pub tie_break: TieBreak,
+    // Record every tick and write the replay here on game over
+    pub replay_path: Option<PathBuf>,
+    // Record every key and write the recording here on game over, for ReplayPlayer
+    pub recording_path: Option<PathBuf>,
+    // Collisions are ignored for this many ticks after the start so the board can be read
+    pub startup_grace_ticks: u32,
+    pub lives: u8,
+    // Collisions are ignored for this many ticks after losing a life, while the frog flashes
+    pub invincibility_ticks: u8,
+    // How long the tick rate takes to ramp up to full speed at the start of a run
+    pub warmup: Duration,
+    pub theme: ThemePreset,
+    // Swap the frog for the theme's last-life glyph when one life is left
+    pub last_life_glyph: bool,
+    // Write the final board here on game over
+    pub export_board_path: Option<PathBuf>,
+    // Hard mode: some grass rows are generated as ice the frog slides across
+    pub ice_rows: bool,
+    // Log (tick, position, input, board hash) every frame for reproducing bugs
+    pub trace_path: Option<PathBuf>,
+    // Per-column spawn chance for cars and pads as (column, width) -> density
+    pub column_density: Option<fn(usize, usize) -> f64>,
+    // Combine every key pressed during a frame with resolve_inputs instead of one key per frame
+    pub resolve_multi_keys: bool,
+    // Pulse the HUD every time this board row shifts, to learn its rhythm
+    pub metronome_row: Option<usize>,
+    // Also ring the terminal bell on each pulse
+    pub metronome_bell: bool,
+    pub bottom_edge: EdgeBehavior,
+    // Collisions never cost a life
+    pub invincible: bool,
+    // Draw the score and lives line under the board
+    pub show_hud: bool,
+    // Scroll the board down a row every this many ticks, pushing the frog along
+    pub auto_scroll_ticks: Option<u64>,
+    // Fixed seed for reproducible runs, otherwise one is picked at random
+    pub seed: Option<u64>,
+    // Start in a seeded random column of START_SPAN instead of the centre
+    pub random_start_column: bool,
+    // Refuse forward moves onto a currently lethal cell instead of letting the frog die
+    pub safe_landing_assist: bool,
+    // Hold the world still until the first key arrives (or this long passes), since the
+    // terminal can take a moment to start delivering input
+    pub ready_timeout: Option<Duration>,
+    // Play a finite generated level instead of an endless board
+    pub level: Option<LevelConfig>,
+    // Frames that take longer than this get their timings written to slow_frames.log
+    pub tick_budget: Option<Duration>,
+    // Give every car and log its own background colour so it can be followed across shifts
+    pub track_vehicles: bool,
+    // Sometimes replace a stream with two flowing ones that need well-timed hops to cross
+    pub two_step_rivers: bool,
+    // Show how close the score is to the next difficulty level under the HUD
+    pub show_difficulty_progress: bool,
+    pub power_up_rules: PowerUpRules,
+    // Draw cars and pads coming in over the edge as fading in; collisions are unaffected
+    pub fade_in: bool,
+    // Loop the world over this many row kinds instead of generating new ones forever
+    pub wrap_world: Option<usize>,
+    // Reroll new rows that cut the frog off from the top of the board
+    pub validate_board_passability: bool,
+    // Where scores, persistent stats and debug logs are kept; None keeps nothing on disk
+    pub config_dir: Option<PathBuf>,
+    // Where the session log is appended; None skips it
+    pub data_dir: Option<PathBuf>,
+    pub board: BoardConfig,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            turn_based: false,
+            min_cars_per_road: 1,
+            collision_flash: true,
+            allow_up: true,
+            allow_down: true,
+            allow_left: true,
+            allow_right: true,
+            viewport_width: None,
+            camera_deadzone: 3,
+            high_score_policy: HighScorePolicy::default(),
+            tie_break: TieBreak::default(),
+            replay_path: None,
+            recording_path: None,
+            startup_grace_ticks: 20,
+            lives: 3,
+            invincibility_ticks: 60,
+            validate_board_passability: true,
+            warmup: Duration::from_secs(3),
+            theme: ThemePreset::Day,
+            last_life_glyph: false,
+            export_board_path: None,
+            ice_rows: false,
+            trace_path: None,
+            column_density: None,
+            resolve_multi_keys: false,
+            metronome_row: None,
+            metronome_bell: false,
+            bottom_edge: EdgeBehavior::default(),
+            invincible: false,
+            show_hud: true,
+            auto_scroll_ticks: None,
+            seed: None,
+            random_start_column: false,
+            safe_landing_assist: false,
+            ready_timeout: Some(Duration::from_secs(5)),
+            level: None,
+            tick_budget: Some(TICK_INTERVAL),
+            track_vehicles: false,
+            two_step_rivers: false,
+            show_difficulty_progress: false,
+            power_up_rules: PowerUpRules::default(),
+            fade_in: false,
+            wrap_world: None,
+            config_dir: config_dir(),
+            data_dir: data_dir(),
+            board: BoardConfig::default(),
+        }
+    }
+}
+
+impl GameConfig {
+    // Relaxed endless play: nothing can kill the frog, no score, and a slow scroll
+    pub fn zen() -> Self {
+        Self {
+            invincible: true,
+            show_hud: false,
+            auto_scroll_ticks: Some(60),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CauseOfDeath {
+    HitByCar,
+    Drowned,
+    FellOffBoard,
+    // the clock ran out in timed mode
+    TimeExpired,
+}
+
+// What happens when the frog tries to move past the bottom of the board.
+// Block refuses the move like any other blocked move (ending a slide), Die costs a life,
+// and Nothing swallows the key entirely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EdgeBehavior {
+    #[default]
+    Block,
+    Die,
+    Nothing,
+}
+
+// What a cell holds, as opposed to how the frog interacts with it; exactly one per cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellContent {
+    Empty,
+    Obstacle,
+    // a river's rock, drawn over any pad passing beneath it
+    Rock,
+    Coin,
+    PowerUp(PowerUpKind),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerUpKind {
+    // rows stop moving
+    Freeze,
+    // forward hops cover two rows
+    DoubleHop,
+    ExtraLife,
+    // everything ahead of the frog is generated again
+    Reroll,
+    // collisions do nothing
+    Shield,
+    // the game runs at double speed
+    SpeedBoost,
+}
+
+impl PowerUpKind {
+    pub fn random(rng: &mut impl Rng) -> Self {
+        match rng.gen_range(0..6) {
+            0 => PowerUpKind::Freeze,
+            1 => PowerUpKind::DoubleHop,
+            2 => PowerUpKind::ExtraLife,
+            3 => PowerUpKind::Reroll,
+            4 => PowerUpKind::Shield,
+            _ => PowerUpKind::SpeedBoost,
+        }
+    }
+
+    // Ticks the effect lasts when collected at `tick_interval`; None for ones that happen once
+    // on pickup. A speed boost lasts SPEED_BOOST_TIME of boosted, half-interval ticks.
+    pub fn duration(self, tick_interval: Duration) -> Option<u32> {
+        match self {
+            PowerUpKind::Freeze => Some(60),
+            PowerUpKind::DoubleHop => Some(100),
+            PowerUpKind::Shield => Some(90),
+            PowerUpKind::SpeedBoost => {
+                let boosted = (tick_interval.as_millis() / 2).max(1);
+                Some((SPEED_BOOST_TIME.as_millis() / boosted) as u32)
+            }
+            PowerUpKind::ExtraLife | PowerUpKind::Reroll => None,
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            PowerUpKind::Freeze => "❄️",
+            PowerUpKind::DoubleHop => "⏫",
+            PowerUpKind::ExtraLife => "❤️",
+            PowerUpKind::Reroll => "🔄",
+            PowerUpKind::Shield => "🛡️",
+            PowerUpKind::SpeedBoost => "⚡",
+        }
+    }
+}
+
+// Wanders the board a cell at a time, sitting on pads the frog might want. Catching one is
+// worth NPC_BONUS points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NpcFrog {
+    pub position: (usize, usize),
+    // ticks until the next move
+    pub move_timer: u8,
+}
+
+impl NpcFrog {
+    pub fn new(position: (usize, usize), rng: &mut impl Rng) -> Self {
+        Self {
+            position,
+            move_timer: rng.gen_range(5..=15),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveEffect {
+    pub kind: PowerUpKind,
+    pub remaining_ticks: u32,
+}
+
+// What collecting an effect that is already active does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepeatPolicy {
+    // the timer restarts at the full duration
+    #[default]
+    Refresh,
+    // the new duration is added to what is left
+    Extend,
+    // the second pickup is wasted
+    Ignore,
+}
+
+// What collecting a new effect does once max_active effects are running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CapacityPolicy {
+    #[default]
+    EvictOldest,
+    DropNew,
+}
+
+// Different effects always stack with each other, up to max_active at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerUpRules {
+    pub max_active: usize,
+    pub repeat: RepeatPolicy,
+    pub at_capacity: CapacityPolicy,
+}
+
+impl Default for PowerUpRules {
+    fn default() -> Self {
+        Self {
+            max_active: 2,
+            repeat: RepeatPolicy::default(),
+            at_capacity: CapacityPolicy::default(),
+        }
+    }
+}
+
+// What the frog's current cell does to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionResult {
+    Safe,
+    HitByCar,
+    Drowned,
+    BlockedByTree,
+    // the frog isn't on the board at all, which is a bug rather than a death
+    OffBoard,
+}
+
+// What a cell means to the frog, independent of how the row draws it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Passable,
+    // Walls such as trees: moving into one is reverted
+    Blocking,
+    Lethal(CauseOfDeath),
+}
+
+// How long each phase of one pass through the game loop took
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameTiming {
+    pub render: Duration,
+    pub input: Duration,
+    pub update: Duration,
+}
+
+impl FrameTiming {
+    pub fn total(&self) -> Duration {
+        self.render + self.input + self.update
+    }
+
+    // None while the frame stays within budget
+    pub fn slow_frame_warning(&self, budget: Duration) -> Option<String> {
+        if self.total() <= budget {
+            return None;
+        }
+        Some(format!(
+            "slow frame: {:?} over a {:?} budget (render {:?}, input {:?}, update {:?})",
+            self.total(),
+            budget,
+            self.render,
+            self.input,
+            self.update
+        ))
+    }
+}
+
+// Classic goes on until the lives run out; Timed also ends the game when the clock does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    Timed {
+        duration: Duration,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoardConfig {
+    // cells per row
+    pub width: usize,
+    // rows on screen
+    pub height: usize,
+    // ticks between shifts for new moving rows; lower is faster
+    pub initial_interval_range: RangeInclusive<u8>,
+    // chance of each road cell starting with a car
+    pub object_density: f64,
+    // time between ticks at the start of a run; it shortens as the frog moves forward
+    pub tick_interval: Duration,
+    pub mode: GameMode,
+    // Moving off one side brings the frog in at the other, if the cell there is free
+    pub wrap_horizontal: bool,
+    // U or Backspace takes back the frog's last step
+    pub allow_undo_move: bool,
+}
+
+impl Default for BoardConfig {
+    fn default() -> Self {
+        Self {
+            width: 14,
+            height: 7,
+            initial_interval_range: 1..=5,
+            object_density: 0.2,
+            tick_interval: TICK_INTERVAL,
+            mode: GameMode::Classic,
+            wrap_horizontal: false,
+            allow_undo_move: true,
+        }
+    }
+}
+
+impl BoardConfig {
+    pub fn easy() -> Self {
+        Self {
+            width: 12,
+            height: 7,
+            initial_interval_range: 3..=6,
+            object_density: 0.12,
+            tick_interval: Duration::from_millis(60),
+            mode: GameMode::Classic,
+            wrap_horizontal: false,
+            allow_undo_move: true,
+        }
+    }
+
+    pub fn hard() -> Self {
+        Self {
+            width: 16,
+            height: 9,
+            initial_interval_range: 1..=3,
+            object_density: 0.35,
+            tick_interval: Duration::from_millis(40),
+            mode: GameMode::Classic,
+            wrap_horizontal: false,
+            allow_undo_move: false,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "easy" => Some(Self::easy()),
+            "normal" => Some(Self::default()),
+            "hard" => Some(Self::hard()),
+            _ => None,
+        }
+    }
+}
+
+// How a headless run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameResult {
+    pub score: u32,
+    pub ticks_survived: u64,
+    pub cause_of_death: Option<CauseOfDeath>,
+}
+
+// Snapshot of the session counters for embedders, read in one go
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    pub score: u32,
+    pub step_count: u64,
+    pub rows_traversed: u32,
+    pub lives: u8,
+    pub game_over: bool,
+    pub cause_of_death: Option<CauseOfDeath>,
+    pub seed: u64,
+    pub cars_dodged: u32,
+    pub coins: u32,
+    // the current combo multiplier, 1 when there is none
+    pub combo: u32,
+    // index into DIFFICULTY_THRESHOLDS
+    pub difficulty_level: usize,
+    pub speed: u32,
+    pub effects: Vec<PowerUpKind>,
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Io(err) => write!(f, "{}", err),
+            LevelError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl From<io::Error> for LevelError {
+    fn from(err: io::Error) -> Self {
+        LevelError::Io(err)
+    }
+}
+
+// A moving row's timing, so a restored row shifts on the same ticks as the saved one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RowMotion {
+    pub direction: bool,
+    pub interval: u8,
+    pub tick_count: u8,
+}
+
+// One row of a saved board: its objects, timing, whether a stream flows, a river's rocks and
+// a road's traffic light. Pickups and the look and ids of vehicles are not restored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RowSnapshot {
+    pub kind: RowKind,
+    pub objects: Vec<bool>,
+    pub motion: Option<RowMotion>,
+    #[serde(default)]
+    pub flowing: bool,
+    #[serde(default)]
+    pub rocks: Option<Vec<bool>>,
+    #[serde(default)]
+    pub light: Option<TrafficLight>,
+}
+
+impl RowSnapshot {
+    pub fn of(row: &dyn RowType) -> Self {
+        Self {
+            kind: row.kind(),
+            objects: row.get_base_row().objects.clone(),
+            motion: row.dynamic_row().map(|dynamic_row| RowMotion {
+                direction: dynamic_row.direction,
+                interval: dynamic_row.interval,
+                tick_count: dynamic_row.tick_count,
+            }),
+            flowing: row.is_flowing(),
+            rocks: row.rocks().map(<[bool]>::to_vec),
+            light: row.dynamic_row().and_then(|dynamic_row| dynamic_row.light),
+        }
+    }
+
+    pub fn to_row(&self) -> Box<dyn RowType> {
+        let objects = self.objects.clone();
+        let RowMotion {
+            direction,
+            interval,
+            tick_count,
+        } = self.motion.unwrap_or(RowMotion {
+            direction: true,
+            interval: 1,
+            tick_count: 0,
+        });
+        match self.kind {
+            RowKind::Grass => Box::new(Grass::new(objects)),
+            RowKind::Ice => Box::new(Ice::new(objects)),
+            RowKind::Train => {
+                let mut row = TrainRow::new(objects.len(), train_dormant_ticks(0));
+                row.baserow.objects = objects;
+                Box::new(row)
+            }
+            RowKind::Road => {
+                let mut road = Road::new(objects, interval, direction, tick_count);
+                road.dynamic_row.light = self.light;
+                Box::new(road)
+            }
+            RowKind::Stream => {
+                let mut stream = Stream::new(objects, interval, direction, tick_count);
+                stream.flowing = self.flowing;
+                match &self.rocks {
+                    Some(rocks) => Box::new(River {
+                        stream,
+                        rocks: rocks.clone(),
+                    }),
+                    None => Box::new(stream),
+                }
+            }
+        }
+    }
+}
+
+// The parts of a game that change while playing, for saving mid-run and resuming later, along
+// with the board settings it was played with. Rows are stored bottom-up, the same as the live
+// board.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoardSnapshot {
+    pub rows: Vec<RowSnapshot>,
+    pub player: (usize, usize),
+    pub player_score: u32,
+    pub lives: u8,
+    #[serde(default)]
+    pub board: BoardConfig,
+}
+
+// Level files list rows top line first as `<kind> <direction> <interval>:<tick_count> <cells>`,
+// e.g. `road > 3:1 ..#....#......`, where static rows use `-` for direction and timing and
+// cells are `#` for an object and `.` for empty, or `@` for a rock in a stream. Streams whose
+// pads move end with `flowing`, and roads with a traffic light with
+// `light:<red_ticks>:<green_ticks>:<elapsed>:<red|green>`. A `player <column> <row>` line
+// places the frog.
+fn parse_level_row(line_number: usize, line: &str) -> Result<Box<dyn RowType>, LevelError> {
+    let error = |message: &str| LevelError::Parse {
+        line: line_number,
+        message: message.to_string(),
+    };
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let [kind, direction, timing, cells] = fields[..fields.len().min(4)] else {
+        return Err(error(
+            "expected `<kind> <direction> <interval>:<tick_count> <cells>`",
+        ));
+    };
+    let kind = RowKind::from_name(kind).ok_or_else(|| error("unknown row kind"))?;
+    let (objects, rocks): (Vec<bool>, Vec<bool>) = cells
+        .chars()
+        .map(|cell| match cell {
+            '#' => Ok((true, false)),
+            '.' => Ok((false, false)),
+            '@' if kind == RowKind::Stream => Ok((false, true)),
+            _ => Err(error("cells must be `#` or `.`, or `@` in a stream")),
+        })
+        .collect::<Result<Vec<(bool, bool)>, _>>()?
+        .into_iter()
+        .unzip();
+    let mut flowing = false;
+    let mut light = None;
+    for extra in &fields[4..] {
+        match (kind, *extra) {
+            (RowKind::Stream, "flowing") => flowing = true,
+            (RowKind::Road, extra) if extra.starts_with("light:") => {
+                light = Some(parse_traffic_light(extra).ok_or_else(|| {
+                    error("light must be `light:<red_ticks>:<green_ticks>:<elapsed>:<red|green>`")
+                })?);
+            }
+            _ => {
+                return Err(error(
+                    "only streams can be `flowing` and roads have a `light:`",
+                ))
+            }
+        }
+    }
+    match kind {
+        RowKind::Grass => return Ok(Box::new(Grass::new(objects))),
+        RowKind::Ice => return Ok(Box::new(Ice::new(objects))),
+        RowKind::Train => {
+            let mut row = TrainRow::new(objects.len(), train_dormant_ticks(0));
+            row.baserow.objects = objects;
+            return Ok(Box::new(row));
+        }
+        _ => {}
+    }
+    let direction = match direction {
+        ">" => true,
+        "<" => false,
+        _ => return Err(error("direction must be `<` or `>`")),
+    };
+    let (interval, tick_count) = timing
+        .split_once(':')
+        .and_then(|(interval, tick_count)| Some((interval.parse().ok()?, tick_count.parse().ok()?)))
+        .filter(|&(interval, _): &(u8, u8)| interval > 0)
+        .ok_or_else(|| error("timing must be `<interval>:<tick_count>` with interval > 0"))?;
+    Ok(match kind {
+        RowKind::Road => {
+            let mut road = Road::new(objects, interval, direction, tick_count);
+            road.dynamic_row.light = light;
+            Box::new(road)
+        }
+        _ => {
+            let mut stream = Stream::new(objects, interval, direction, tick_count);
+            stream.flowing = flowing;
+            if rocks.contains(&true) {
+                Box::new(River { stream, rocks })
+            } else {
+                Box::new(stream)
+            }
+        }
+    })
+}
+
+// `light:<red_ticks>:<green_ticks>:<elapsed>:<red|green>`, as written by level_row_line
+fn parse_traffic_light(field: &str) -> Option<TrafficLight> {
+    let parts: Vec<&str> = field.strip_prefix("light:")?.split(':').collect();
+    let [red_ticks, green_ticks, elapsed, colour] = parts[..] else {
+        return None;
+    };
+    Some(TrafficLight {
+        red_ticks: red_ticks.parse().ok()?,
+        green_ticks: green_ticks.parse().ok()?,
+        elapsed: elapsed.parse().ok()?,
+        red: match colour {
+            "red" => true,
+            "green" => false,
+            _ => return None,
+        },
+    })
+}
+
+fn level_row_line(row: &dyn RowType) -> String {
+    let rocks = row.rocks().unwrap_or_default();
+    let cells: String = row
+        .get_base_row()
+        .objects
+        .iter()
+        .enumerate()
+        .map(|(column, &obj)| match (obj, rocks.get(column)) {
+            (true, _) => '#',
+            (false, Some(true)) => '@',
+            (false, _) => '.',
+        })
+        .collect();
+    let Some(dynamic_row) = row.dynamic_row() else {
+        return format!("{} - - {}", row.kind().name(), cells);
+    };
+    let mut line = format!(
+        "{} {} {}:{} {}",
+        row.kind().name(),
+        if dynamic_row.direction { '>' } else { '<' },
+        dynamic_row.interval,
+        dynamic_row.tick_count,
+        cells
+    );
+    if row.is_flowing() {
+        line.push_str(" flowing");
+    }
+    if let Some(light) = dynamic_row.light {
+        line.push_str(&format!(
+            " light:{}:{}:{}:{}",
+            light.red_ticks,
+            light.green_ticks,
+            light.elapsed,
+            if light.red { "red" } else { "green" }
+        ));
+    }
+    line
+}
+
+pub struct GameState {
+    config: GameConfig,
+    // every row has exactly this many cells
+    board_width: usize,
+    // the one source of randomness for the whole game, seeded from `seed`
+    rng: StdRng,
+    gameboard: Vec<Box<dyn RowType>>,
+    player: (usize, usize),
+    player_score: u32,
+    checkpoints: [Option<Checkpoint>; CHECKPOINT_SLOTS],
+    theme: Theme,
+    undo_stack: VecDeque<TurnSnapshot>,
+    game_over: bool,
+    collision_flash: u8,
+    // where the frog was when it last lost a life, flashed there until collision_flash runs out
+    collision_at: Option<(usize, usize)>,
+    step_count: u64,
+    rows_traversed: u32,
+    camera_x: usize,
+    replay: Option<Replay>,
+    recorder: Option<ReplayRecorder>,
+    grace_ticks: u32,
+    lives: u8,
+    // counts down after a respawn; collisions do nothing until it reaches zero
+    invincibility_ticks: u8,
+    cause_of_death: Option<CauseOfDeath>,
+    // Game time played so far; only advances while the world is ticking
+    elapsed: Duration,
+    internal_error: Option<String>,
+    // Sideways direction the frog keeps sliding in while on ice
+    slide: Option<Direction>,
+    // the way the frog last moved sideways, which a hop onto ice slides it on in
+    last_horizontal_direction: Option<Direction>,
+    trace: Option<TraceLog>,
+    seed: u64,
+    // Short message shown under the HUD for the current frame
+    feedback: Option<&'static str>,
+    spawner: Box<dyn SpawnStrategy>,
+    // rows scrolled off the bottom, so scrolled_rows + player.1 is the frog's world row
+    scrolled_rows: u64,
+    won: bool,
+    // timed power-ups, oldest first
+    effects: Vec<ActiveEffect>,
+    npcs: Vec<NpcFrog>,
+    high_score_store: HighScoreStore,
+    renderer: Box<dyn Renderer>,
+    key_map: KeyMap,
+    // while paused nothing moves and only the pause and quit keys do anything
+    paused: bool,
+    quit: bool,
+    // multiplier for the next forward hop; it grows with each hop and drops back to 1 after
+    // COMBO_TIMEOUT_TICKS ticks without one
+    combo: u32,
+    combo_timeout: u8,
+    stats: SessionStats,
+    // real time between ticks, shrinking every SPEED_UP_EVERY forward hops and SPEED_UP_STEPS ticks
+    tick_interval: Duration,
+    // timed mode only: game time left, one tick_interval comes off each tick
+    time_remaining: Option<Duration>,
+    // kinds of the rows generated so far, newest last
+    row_history: RowHistory,
+    // where the frog was before its last step and what that step scored, for undo_move
+    previous_player_pos: Option<(usize, usize)>,
+    previous_move_points: u32,
+    // the hint being drawn and the ticks left to show it
+    hint: HintPath,
+    hint_ticks: u32,
+    // the board got hard enough that the next grass row gets a power-up
+    power_up_owed: bool,
+    // fast roads and near-empty streams since the last grass row
+    consecutive_hard_rows: u8,
+    // the next new row is grass, to give the frog somewhere to breathe
+    safe_row_owed: bool,
+    // ticks the frog has spent swept along in open water, reset once it is out
+    water_drift_grace: u8,
+    // -1 blowing left, 0 calm, 1 blowing right
+    wind_direction: i8,
+    // ticks until the next gust
+    wind_timer: u8,
+    // set by the Ctrl-C handler, checked once a frame
+    shutdown: Arc<AtomicBool>,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameState {
+    pub fn new() -> Self {
+        Self::from_config(GameConfig::default())
+    }
+
+    // Draws to the terminal but never reads from it: input is whatever InputSource gets passed
+    // to run, or comes from tick and run_scripted
+    pub fn from_config(config: GameConfig) -> Self {
+        Self::build(config, Box::new(TerminalRenderer::new()))
+    }
+
+    // Doesn't touch the terminal at all
+    pub fn headless(config: GameConfig) -> Self {
+        Self::build(config, Box::new(StringRenderer::default()))
+    }
+
+    // Same seed and same inputs give the same game
+    pub fn new_seeded(seed: u64) -> Self {
+        Self::from_config(GameConfig {
+            seed: Some(seed),
+            ..GameConfig::default()
+        })
+    }
+
+    // Timed mode on the default board, with this many seconds on the clock
+    pub fn new_timed(seconds: u64) -> Self {
+        Self::new_with_config(BoardConfig {
+            mode: GameMode::Timed {
+                duration: Duration::from_secs(seconds),
+            },
+            ..BoardConfig::default()
+        })
+    }
+
+    // A board of the given size with everything else left at its default
+    pub fn new_with_config(board: BoardConfig) -> Self {
+        Self::from_config(GameConfig {
+            board,
+            ..GameConfig::default()
+        })
+    }
+
+    // Boards narrower than one cell or shorter than the two starting rows are grown to fit,
+    // and an unusable interval range falls back to the default one
+    fn build(mut config: GameConfig, renderer: Box<dyn Renderer>) -> Self {
+        config.board.width = config.board.width.max(1);
+        config.board.height = config.board.height.max(2);
+        let intervals = &config.board.initial_interval_range;
+        if intervals.is_empty() || *intervals.start() == 0 {
+            config.board.initial_interval_range = BoardConfig::default().initial_interval_range;
+        }
+        let theme = Theme::preset(config.theme);
+        let trace = config.trace_path.as_ref().and_then(|path| {
+            TraceLog::open(path)
+                .map_err(|err| eprintln!("Could not open trace {}: {}", path.display(), err))
+                .ok()
+        });
+        let spawner: Box<dyn SpawnStrategy> = match (config.level, config.wrap_world) {
+            (Some(level_config), _) => Box::new(Level::new(level_config, config.board.width)),
+            (None, Some(pool_size)) => Box::new(WrapAroundSpawn::new(config.clone(), pool_size)),
+            (None, None) => Box::new(RandomSpawn::new(config.clone())),
+        };
+        let high_score_store = HighScoreStore::load(config.config_dir.as_deref());
+        // everything that belongs to a single run is set up by new_game
+        let mut state = Self {
+            board_width: config.board.width,
+            gameboard: Vec::with_capacity(config.board.height),
+            config,
+            player: (0, 0),
+            rng: StdRng::seed_from_u64(0),
+            player_score: 0,
+            checkpoints: Default::default(),
+            theme,
+            undo_stack: VecDeque::with_capacity(UNDO_DEPTH),
+            game_over: false,
+            collision_flash: 0,
+            collision_at: None,
+            step_count: 0,
+            rows_traversed: 0,
+            camera_x: 0,
+            replay: None,
+            recorder: None,
+            grace_ticks: 0,
+            lives: 0,
+            invincibility_ticks: 0,
+            cause_of_death: None,
+            elapsed: Duration::ZERO,
+            internal_error: None,
+            slide: None,
+            last_horizontal_direction: None,
+            trace,
+            seed: 0,
+            feedback: None,
+            spawner,
+            scrolled_rows: 0,
+            won: false,
+            effects: Vec::new(),
+            npcs: Vec::new(),
+            high_score_store,
+            renderer,
+            key_map: KeyMap::default(),
+            paused: false,
+            quit: false,
+            combo: 1,
+            combo_timeout: 0,
+            stats: SessionStats::default(),
+            tick_interval: Duration::ZERO,
+            time_remaining: None,
+            row_history: RowHistory::default(),
+            previous_player_pos: None,
+            previous_move_points: 0,
+            hint: Vec::new(),
+            hint_ticks: 0,
+            power_up_owed: false,
+            consecutive_hard_rows: 0,
+            safe_row_owed: false,
+            water_drift_grace: 0,
+            wind_direction: 0,
+            wind_timer: WIND_GUST_TICKS,
+            shutdown: Arc::new(AtomicBool::new(false)),
+        };
+        state.new_game();
+        state
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Only rows spawned from now on come from the new strategy
+    pub fn with_spawn_strategy(mut self, spawner: impl SpawnStrategy + 'static) -> Self {
+        self.spawner = Box::new(spawner);
+        self
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub fn with_key_map(mut self, key_map: KeyMap) -> Self {
+        self.key_map = key_map;
+        self
+    }
+
+    pub fn with_renderer(mut self, renderer: impl Renderer + 'static) -> Self {
+        self.renderer = Box::new(renderer);
+        self
+    }
+
+    // Starts a new run in place: a fresh board from the config's seed (or a random one) and
+    // every per-run counter back at its start. The config, renderer, theme, key bindings, spawn
+    // strategy, trace and high scores carry over, and the new rows go into the old board's
+    // allocation.
+    pub fn new_game(&mut self) {
+        let config = &self.config;
+        let (width, height) = (config.board.width, config.board.height);
+        self.seed = config.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let start_column = if config.random_start_column {
+            rng.gen_range(START_SPAN).min(width - 1)
+        } else {
+            width / 2
+        };
+        let mut bottom_row =
+            BaseRow::randomized_objects(width, TREE, GRASS, GRASS_DENSITY, &mut rng);
+        for column in START_SPAN.filter(|&column| column < width) {
+            bottom_row.objects[column] = false;
+        }
+        bottom_row.objects[start_column] = false;
+        // the first step forward must be possible too
+        let mut second_row =
+            BaseRow::randomized_objects(width, TREE, GRASS, GRASS_DENSITY, &mut rng);
+        second_row.objects[start_column] = false;
+        self.gameboard.clear();
+        self.gameboard
+            .push(Box::new(Grass::new(bottom_row.objects)));
+        self.gameboard
+            .push(Box::new(Grass::new(second_row.objects)));
+        self.spawner.reset(&mut rng);
+        self.spawner.set_score(0);
+        // the cap on hard rows in a row holds from the first board, as in update_stack
+        let endless = config.level.is_none() && config.wrap_world.is_none();
+        let mut hard_rows = 0;
+        while self.gameboard.len() < height {
+            let history: Vec<RowKind> = self.gameboard.iter().map(|row| row.kind()).collect();
+            let row = if endless && hard_rows >= MAX_CONSECUTIVE_HARD_ROWS {
+                GameState::create_row_of_kind(config, RowKind::Grass, &mut rng)
+            } else {
+                self.spawner.next_row(&history, &mut rng)
+            };
+            hard_rows = GameState::count_hard_row(hard_rows, row.as_ref());
+            self.gameboard.push(row);
+        }
+        let kinds: Vec<RowKind> = self.gameboard.iter().map(|row| row.kind()).collect();
+        self.row_history = RowHistory::of(&kinds);
+        self.consecutive_hard_rows = hard_rows;
+        self.replay = config.replay_path.as_ref().map(|_| Replay::default());
+        self.recorder = config
+            .recording_path
+            .is_some()
+            .then(|| ReplayRecorder::new(self.seed));
+        self.grace_ticks = config.startup_grace_ticks;
+        self.lives = config.lives;
+        self.tick_interval = config.board.tick_interval;
+        self.time_remaining = match config.board.mode {
+            GameMode::Classic => None,
+            GameMode::Timed { duration } => Some(duration),
+        };
+        self.rng = rng;
+        self.player = (start_column, 0);
+        self.player_score = 0;
+        self.checkpoints = Default::default();
+        self.undo_stack.clear();
+        self.game_over = false;
+        self.collision_flash = 0;
+        self.collision_at = None;
+        self.step_count = 0;
+        self.rows_traversed = 0;
+        self.camera_x = 0;
+        self.invincibility_ticks = 0;
+        self.cause_of_death = None;
+        self.elapsed = Duration::ZERO;
+        self.internal_error = None;
+        self.slide = None;
+        self.last_horizontal_direction = None;
+        self.feedback = None;
+        self.scrolled_rows = 0;
+        self.won = false;
+        self.effects.clear();
+        self.npcs.clear();
+        self.paused = false;
+        self.quit = false;
+        self.combo = 1;
+        self.combo_timeout = 0;
+        self.stats = SessionStats::default();
+        self.previous_player_pos = None;
+        self.previous_move_points = 0;
+        self.hint.clear();
+        self.hint_ticks = 0;
+        self.power_up_owed = false;
+        self.safe_row_owed = false;
+        self.water_drift_grace = 0;
+        self.wind_direction = 0;
+        self.wind_timer = WIND_GUST_TICKS;
+    }
+
+    // Update stack will create random row, remove first row, and push new row
+    pub fn create_random_row(
+        config: &GameConfig,
+        history: RowHistory,
+        mut rng: &mut impl Rng,
+    ) -> Box<dyn RowType> {
+        let kind = choose_row_kind(history, &mut rng);
+        GameState::create_row_of_kind(config, kind, rng)
+    }
+
+    pub fn create_random_row_with_cfg(
+        cfg: &BoardConfig,
+        history: RowHistory,
+        rng: &mut impl Rng,
+    ) -> Box<dyn RowType> {
+        let config = GameConfig {
+            board: cfg.clone(),
+            ..GameConfig::default()
+        };
+        GameState::create_random_row(&config, history, rng)
+    }
+
+    pub fn create_row_of_kind(
+        config: &GameConfig,
+        kind: RowKind,
+        mut rng: &mut impl Rng,
+    ) -> Box<dyn RowType> {
+        let width = config.board.width;
+        let interval = rng.gen_range(config.board.initial_interval_range.clone());
+        let direction = rng.gen_bool(0.5);
+        let phase = rng.gen_range(0..interval);
+        let mut objects = match (config.column_density, kind) {
+            (_, RowKind::Grass) => {
+                BaseRow::randomized_objects(width, TREE, GRASS, GRASS_DENSITY, &mut rng).objects
+            }
+            (None, RowKind::Stream) => {
+                let (size, groups) = (STREAM_GROUP_SIZE, STREAM_GROUPS);
+                BaseRow::randomized_objects_grouped(width, TREE, GRASS, size, groups, &mut rng)
+                    .objects
+            }
+            (Some(curve), RowKind::Stream) => {
+                let density = |column| curve(column, width);
+                let (size, groups) = (STREAM_GROUP_SIZE, STREAM_GROUPS);
+                BaseRow::randomized_objects_grouped_by_column(
+                    width, TREE, GRASS, size, groups, density, &mut rng,
+                )
+                .objects
+            }
+            (None, RowKind::Road) => {
+                let size = rng.gen_range(VEHICLE_LENGTH);
+                let cars = width as f64 * config.board.object_density.clamp(0.0, 1.0);
+                let groups = (cars / size as f64).round() as usize;
+                let (size, groups) = vehicle_groups(width, config.min_cars_per_road, size, groups);
+                BaseRow::randomized_objects_grouped(width, TREE, GRASS, size, groups, &mut rng)
+                    .objects
+            }
+            (Some(curve), RowKind::Road) => {
+                let density = |column| curve(column, width).clamp(0.0, 1.0);
+                let size = rng.gen_range(VEHICLE_LENGTH);
+                let cars: f64 = (0..width).map(density).sum();
+                let groups = (cars / size as f64).round() as usize;
+                let (size, groups) = vehicle_groups(width, config.min_cars_per_road, size, groups);
+                BaseRow::randomized_objects_grouped_by_column(
+                    width, TREE, GRASS, size, groups, density, &mut rng,
+                )
+                .objects
+            }
+            (Some(curve), _) => {
+                let density = |column| curve(column, width);
+                BaseRow::randomized_objects_by_column(width, TREE, GRASS, density, &mut rng).objects
+            }
+            (None, _) => {
+                BaseRow::randomized_objects_with_cfg(&config.board, TREE, GRASS, &mut rng).objects
+            }
+        };
+        if kind == RowKind::Grass {
+            BaseRow::ensure_gap(&mut objects, MIN_GAP_WIDTH, &mut rng);
+        }
+
+        match kind {
+            RowKind::Stream if rng.gen_bool(RIVER_CHANCE) => {
+                Box::new(River::new(objects, interval, direction, phase, &mut rng))
+            }
+            RowKind::Stream => Box::new(Stream::new(objects, interval, direction, phase).flowing()),
+            RowKind::Road if rng.gen_bool(0.2) => Box::new(
+                Road::new(objects, interval, direction, phase)
+                    .with_mixed_vehicles(&mut rng)
+                    .with_traffic_light(rng.gen_range(10..=20), rng.gen_range(20..=40)),
+            ),
+            RowKind::Road if rng.gen_bool(0.1) => Box::new(
+                Road::new(objects, interval, direction, phase)
+                    .with_mixed_vehicles(&mut rng)
+                    .with_traffic_mode(TrafficMode::Bounce),
+            ),
+            RowKind::Road => Box::new(
+                Road::new(objects, interval, direction, phase).with_mixed_vehicles(&mut rng),
+            ),
+            RowKind::Train => Box::new(TrainRow::new(width, train_dormant_ticks(0))),
+            _ if config.ice_rows && rng.gen_bool(0.3) => Box::new(Ice::new(objects)),
+            _ => Box::new(
+                Grass::with_coins(objects, COIN_CHANCE, &mut rng)
+                    .with_power_ups(POWER_UP_CHANCE, &mut rng),
+            ),
+        }
+    }
+
+    // Regenerates until the constraint holds. Once retries run out an empty grass row is used,
+    // which satisfies any sensible constraint, so generation can never loop forever.
+    pub fn generate_row_with_constraints(
+        spawner: &mut dyn SpawnStrategy,
+        history: &[RowKind],
+        constraint: impl Fn(&dyn RowType) -> bool,
+        max_retries: usize,
+        width: usize,
+        rng: &mut dyn RngCore,
+    ) -> Box<dyn RowType> {
+        for _ in 0..=max_retries {
+            let row = spawner.next_row(history, rng);
+            if constraint(row.as_ref()) {
+                return row;
+            }
+        }
+        Box::new(Grass::new(vec![false; width]))
+    }
+
+    // A grass or ice row made entirely of trees can never be crossed
+    pub fn has_open_cell(row: &dyn RowType) -> bool {
+        !matches!(row.kind(), RowKind::Grass | RowKind::Ice)
+            || row.get_base_row().objects.iter().any(|&obj| !obj)
+    }
+
+    // Plays a copy of the row forward looking for a tick with somewhere safe to stand. A road
+    // packed with cars or a stream without pads never has one, and rocks don't count.
+    pub fn is_row_crossable(row: &dyn RowType) -> bool {
+        let mut row = row.clone_row();
+        let width = row.get_base_row().objects.len();
+        for _ in 0..CROSSABLE_CHECK_TICKS {
+            if (0..width).any(|column| row.cell(column) == Some(Cell::Passable)) {
+                return true;
+            }
+            row.tick();
+        }
+        false
+    }
+
+    // Whether the frog can walk from where it stands to the top row with every object frozen
+    // where it is now. Trees and water without a pad are walls; cars are left out since they
+    // move out of the way.
+    pub fn is_board_passable(&self) -> bool {
+        let top = self.gameboard.len() - 1;
+        let open = |column: usize, row_index: usize| {
+            !matches!(
+                self.cell(column, row_index),
+                None | Some(Cell::Blocking) | Some(Cell::Lethal(CauseOfDeath::Drowned))
+            )
+        };
+        let mut seen = HashSet::from([self.player]);
+        let mut queue = VecDeque::from([self.player]);
+        while let Some((column, row_index)) = queue.pop_front() {
+            if row_index == top {
+                return true;
+            }
+            let neighbours = [
+                (column, row_index + 1),
+                (column, row_index.wrapping_sub(1)),
+                (column.wrapping_sub(1), row_index),
+                (column + 1, row_index),
+            ];
+            for (column, row_index) in neighbours {
+                if open(column, row_index) && seen.insert((column, row_index)) {
+                    queue.push_back((column, row_index));
+                }
+            }
+        }
+        false
+    }
+
+    // A road whose cars shift every tick or two, or a stream with fewer than two pads
+    pub fn is_hard_row(row: &dyn RowType) -> bool {
+        match row.kind() {
+            RowKind::Road => row
+                .dynamic_row()
+                .is_some_and(|moving| moving.interval() <= 2),
+            RowKind::Stream => row.get_base_row().object_count() < 2,
+            _ => false,
+        }
+    }
+
+    // The hard-row count once `row` is added on top: grass and ice reset it
+    fn count_hard_row(count: u8, row: &dyn RowType) -> u8 {
+        match row.kind() {
+            RowKind::Grass | RowKind::Ice => 0,
+            _ if GameState::is_hard_row(row) => count.saturating_add(1),
+            _ => count,
+        }
+    }
+
+    pub fn consecutive_hard_rows(&self) -> u8 {
+        self.consecutive_hard_rows
+    }
+
+    // The constraint every generated row has to meet
+    pub fn is_fair_row(row: &dyn RowType) -> bool {
+        GameState::has_open_cell(row) && GameState::is_row_crossable(row)
+    }
+
+    pub fn screen_line(&self, row_index: usize) -> Option<usize> {
+        ROW_ORDERING.screen_line(row_index, self.gameboard.len())
+    }
+
+    pub fn board_width(&self) -> usize {
+        self.board_width
+    }
+
+    // Short boards scroll sooner so there is always a row to hop into
+    fn scroll_row(&self) -> usize {
+        SCROLL_ROW.min(self.gameboard.len().saturating_sub(2))
+    }
+
+    pub fn row(&self, row_index: usize) -> Option<&dyn RowType> {
+        self.gameboard.get(row_index).map(|row| row.as_ref())
+    }
+
+    // None if the cell is off the board
+    pub fn cell(&self, column: usize, row_index: usize) -> Option<Cell> {
+        self.row(row_index)?.cell(column)
+    }
+
+    // Range of board columns currently drawn
+    pub fn visible_columns(&self) -> std::ops::Range<usize> {
+        let width = self.board_width();
+        match self.config.viewport_width {
+            Some(viewport_width) if viewport_width < width => {
+                self.camera_x..self.camera_x + viewport_width
+            }
+            _ => 0..width,
+        }
+    }
+
+    // Scroll just far enough that the frog is back inside the deadzone
+    pub fn update_camera(&mut self) {
+        let width = self.board_width();
+        let viewport_width = match self.config.viewport_width {
+            Some(viewport_width) if viewport_width < width => viewport_width,
+            _ => {
+                self.camera_x = 0;
+                return;
+            }
+        };
+        let deadzone = self.config.camera_deadzone.min((viewport_width - 1) / 2);
+        let column = self.player.0;
+        if column < self.camera_x + deadzone {
+            self.camera_x = column.saturating_sub(deadzone);
+        } else if column + deadzone >= self.camera_x + viewport_width {
+            self.camera_x = column + deadzone + 1 - viewport_width;
+        }
+        self.camera_x = self.camera_x.min(width - viewport_width);
+    }
+
+    // The renderer is taken out for the call so it can look at the rest of the state. Output
+    // that can't be written to any more, e.g. a closed terminal, ends the run at the next frame.
+    pub fn print_gameboard(&mut self) {
+        if self.shutdown.load(Ordering::SeqCst) {
+            return;
+        }
+        let mut renderer =
+            std::mem::replace(&mut self.renderer, Box::new(StringRenderer::default()));
+        let result = renderer.render_frame(self);
+        self.renderer = renderer;
+        if let Err(err) = result {
+            eprintln!("Could not draw the board: {}", err);
+            self.shutdown.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn speed_up(&mut self) {
+        self.tick_interval = self
+            .tick_interval
+            .saturating_sub(SPEED_UP_STEP)
+            .max(MIN_TICK_INTERVAL.min(self.config.board.tick_interval));
+    }
+
+    // Ticks played so far; meta keys and paused ticks don't count
+    pub fn step_count(&self) -> u64 {
+        self.step_count
+    }
+
+    pub fn tick_interval(&self) -> Duration {
+        self.tick_interval
+    }
+
+    // 1 at the starting interval, one more for each speed-up
+    pub fn speed(&self) -> u32 {
+        let start = self.config.board.tick_interval;
+        let faster_by = start.saturating_sub(self.tick_interval);
+        let steps = faster_by.as_millis() / SPEED_UP_STEP.as_millis();
+        (1 + steps).min(MAX_SPEED as u128) as u32
+    }
+
+    pub fn combo(&self) -> u32 {
+        self.combo
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn restore_terminal(&mut self) {
+        self.renderer.restore();
+    }
+
+    pub fn render_to_string(&self) -> String {
+        StringRenderer::default().compose_frame(self)
+    }
+
+    // The board and everything under it, without the border the renderers add
+    pub fn frame(&self) -> String {
+        let mut frame = self.render_board();
+        frame.push_str(&StringRenderer::default().render_hud(&self.current_hud(), &self.theme));
+        frame
+    }
+
+    // What the HUD shows this frame
+    pub fn current_hud(&self) -> Hud {
+        let best = self.high_score_store.high_score().unwrap_or(0);
+        Hud {
+            show_stats: self.config.show_hud,
+            score: self.player_score,
+            best: best.max(self.player_score),
+            lives: self.lives,
+            speed: self.speed(),
+            time_remaining: self.time_remaining,
+            wind: self.wind_direction,
+            combo: (self.combo > 1).then(|| self.combo.min(MAX_COMBO)),
+            effects: self.effects.iter().map(|effect| effect.kind).collect(),
+            rows_remaining: self.rows_remaining(),
+            difficulty: self
+                .config
+                .show_difficulty_progress
+                .then(|| self.difficulty_line()),
+            feedback: self.feedback,
+            metronome_pulse: self.metronome_pulse(),
+            paused: self.paused,
+        }
+    }
+
+    // "Lv 2 [████▌    ]" sized to span the board
+    pub fn difficulty_line(&self) -> String {
+        let difficulty = difficulty_params(self.player_score);
+        let label = format!("Lv {} [", difficulty.level + 1);
+        let board_width = self.visible_columns().len() * self.theme.cell_width();
+        let bar_width = board_width.saturating_sub(label.len() + 1).max(1);
+        let bar = progress_bar(difficulty.progress(self.player_score), bar_width);
+        format!("{}{}]", label, bar)
+    }
+
+    // True on the frames where the metronome's row shifted
+    pub fn metronome_pulse(&self) -> bool {
+        self.config
+            .metronome_row
+            .and_then(|row_index| self.row(row_index))
+            .and_then(|row| row.dynamic_row())
+            .is_some_and(|dynamic_row| dynamic_row.shifted())
+    }
+
+    // The board as text, one line per row, top row first
+    pub fn render_board(&self) -> String {
+        self.render_board_with(&self.theme)
+    }
+
+    pub fn render_board_with(&self, theme: &Theme) -> String {
+        let player_line = self.screen_line(self.player.1);
+        let columns = self.visible_columns();
+        // an invincible frog blinks out on odd ticks
+        let player_label = if !self.invincibility_ticks.is_multiple_of(2) {
+            ' '
+        } else if self.config.last_life_glyph && self.lives == 1 {
+            HURT_FROG
+        } else {
+            FROG
+        };
+
+        let mut lines = vec![String::new(); self.gameboard.len()];
+        for (row_index, row) in self.gameboard.iter().enumerate() {
+            let Some(line_index) = self.screen_line(row_index) else {
+                continue;
+            };
+            let line = &mut lines[line_index];
+            for (col_index, &obj) in row.get_base_row().objects.iter().enumerate() {
+                if !columns.contains(&col_index) {
+                    continue;
+                }
+                // the frog also flashes where it was hit, alternating with what hit it: on odd
+                // flash frames that cell is shown instead
+                let at_player = Some(line_index) == player_line && col_index == self.player.0;
+                let at_collision =
+                    self.collision_flash > 0 && self.collision_at == Some((col_index, row_index));
+                let show_hit = at_collision && !self.collision_flash.is_multiple_of(2);
+                if at_player && !show_hit {
+                    line.push_str(&theme.cell(player_label));
+                } else if at_collision && !show_hit {
+                    line.push_str(&theme.cell(HURT_FROG));
+                } else if self.npc_at((col_index, row_index)) {
+                    line.push_str(&theme.cell(NPC));
+                } else if let Some(&(_, direction)) = self
+                    .hint
+                    .iter()
+                    .find(|&&(cell, _)| cell == (col_index, row_index))
+                {
+                    line.push_str(&theme.cell(direction.arrow()));
+                } else {
+                    let fade = row.dynamic_row().filter(|_| self.config.fade_in);
+                    let cell = theme.cell(match row.content(col_index) {
+                        CellContent::Coin => COIN,
+                        CellContent::PowerUp(_) => POWER_UP,
+                        CellContent::Rock => ROCK,
+                        _ if obj => match fade.and_then(|moving| moving.fade_stage(col_index)) {
+                            Some(stage) => FADE_GLYPHS[stage + 1],
+                            None => row.object_label(col_index),
+                        },
+                        _ if fade.and_then(|moving| moving.incoming()) == Some(col_index) => {
+                            FADE_GLYPHS[0]
+                        }
+                        _ => row.get_base_row().environment_label,
+                    });
+                    match row.object_id(col_index) {
+                        Some(id) if self.config.track_vehicles => {
+                            let color = VEHICLE_COLORS[id as usize % VEHICLE_COLORS.len()];
+                            line.push_str(&style(cell).on_color256(color).to_string());
+                        }
+                        _ => line.push_str(&cell),
+                    }
+                }
+            }
+            if let Some(glyph) = row.edge_glyph() {
+                line.push(theme.glyph(glyph));
+            }
+        }
+        let mut board = String::with_capacity(lines.iter().map(|line| line.len() + 1).sum());
+        for line in lines {
+            board.push_str(&line);
+            board.push('\n');
+        }
+        board
+    }
+
+    // Every row's raw labels in screen order with a P where the frog is; no theme, pickups or
+    // NPCs, just what the rows hold
+    pub fn dump_board(&self) -> String {
+        let mut lines = vec![String::new(); self.gameboard.len()];
+        for (row_index, row) in self.gameboard.iter().enumerate() {
+            let Some(line_index) = self.screen_line(row_index) else {
+                continue;
+            };
+            lines[line_index] = row
+                .get_base_row()
+                .to_string()
+                .chars()
+                .enumerate()
+                .map(|(column, label)| {
+                    if (column, row_index) == self.player {
+                        'P'
+                    } else {
+                        label
+                    }
+                })
+                .collect();
+        }
+        lines.join("\n")
+    }
+
+    pub fn export_board(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.render_board())
+    }
+
+    pub fn from_level_file(path: &Path, config: GameConfig) -> Result<Self, LevelError> {
+        Self::from_level_str(&fs::read_to_string(path)?, config)
+    }
+
+    pub fn from_level_str(level: &str, config: GameConfig) -> Result<Self, LevelError> {
+        let mut rows = Vec::new();
+        let mut player = None;
+        for (index, line) in level.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(position) = line.strip_prefix("player ") {
+                let parsed: Vec<usize> = position
+                    .split_whitespace()
+                    .filter_map(|field| field.parse().ok())
+                    .collect();
+                let [column, row] = parsed[..] else {
+                    return Err(LevelError::Parse {
+                        line: index + 1,
+                        message: "expected `player <column> <row>`".to_string(),
+                    });
+                };
+                player = Some((column, row));
+                continue;
+            }
+            rows.push(parse_level_row(index + 1, line)?);
+        }
+        let parse_error = |message: &str| LevelError::Parse {
+            line: 0,
+            message: message.to_string(),
+        };
+        if rows.is_empty() {
+            return Err(parse_error("level has no rows"));
+        }
+        let width = rows[0].get_base_row().objects.len();
+        if rows
+            .iter()
+            .any(|row| row.get_base_row().objects.len() != width)
+        {
+            return Err(parse_error("all rows must be the same width"));
+        }
+        // the file lists the top row first but the board is stored bottom-up
+        rows.reverse();
+        let player = player.unwrap_or((width / 2, 0));
+        if player.0 >= width || player.1 >= rows.len() {
+            return Err(parse_error("player is outside the board"));
+        }
+        let mut config = config;
+        config.board = BoardConfig {
+            width,
+            height: rows.len(),
+            ..config.board
+        };
+        let mut state = Self::from_config(config);
+        state.gameboard = rows;
+        state.player = player;
+        Ok(state)
+    }
+
+    pub fn snapshot(&self) -> BoardSnapshot {
+        BoardSnapshot {
+            rows: self
+                .gameboard
+                .iter()
+                .map(|row| RowSnapshot::of(row.as_ref()))
+                .collect(),
+            player: self.player,
+            player_score: self.player_score,
+            lives: self.lives,
+            board: self.config.board.clone(),
+        }
+    }
+
+    pub fn from_snapshot(snapshot: BoardSnapshot) -> Result<Self, LevelError> {
+        Self::from_snapshot_with_config(snapshot, GameConfig::default())
+    }
+
+    // Snapshots may come from a file, so they get the same checks as a level. The board
+    // settings come from the snapshot and everything else from config.
+    pub fn from_snapshot_with_config(
+        snapshot: BoardSnapshot,
+        config: GameConfig,
+    ) -> Result<Self, LevelError> {
+        let error = |message: &str| LevelError::Parse {
+            line: 0,
+            message: message.to_string(),
+        };
+        let Some(width) = snapshot.rows.first().map(|row| row.objects.len()) else {
+            return Err(error("snapshot has no rows"));
+        };
+        if width == 0 || snapshot.rows.iter().any(|row| row.objects.len() != width) {
+            return Err(error("all rows must be the same, non-zero width"));
+        }
+        let mut rocks = snapshot.rows.iter().filter_map(|row| row.rocks.as_ref());
+        if rocks.any(|rocks| rocks.len() != width) {
+            return Err(error("rocks must cover the whole row"));
+        }
+        let motions = snapshot.rows.iter().filter_map(|row| row.motion);
+        if motions
+            .map(|motion| motion.interval)
+            .any(|interval| interval == 0)
+        {
+            return Err(error("row intervals must be at least 1"));
+        }
+        let (column, row) = snapshot.player;
+        if column >= width || row >= snapshot.rows.len() {
+            return Err(error("player is outside the board"));
+        }
+        let mut state = Self::from_config(GameConfig {
+            board: BoardConfig {
+                width,
+                height: snapshot.rows.len(),
+                ..snapshot.board
+            },
+            ..config
+        });
+        state.gameboard = snapshot.rows.iter().map(RowSnapshot::to_row).collect();
+        state.player = snapshot.player;
+        state.player_score = snapshot.player_score;
+        state.lives = snapshot.lives;
+        Ok(state)
+    }
+
+    // Hash of the board contents, row timing and player, for spotting where two runs diverge
+    pub fn board_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for row in &self.gameboard {
+            row.kind().hash(&mut hasher);
+            row.get_base_row().objects.hash(&mut hasher);
+            if let Some(dynamic_row) = row.dynamic_row() {
+                dynamic_row.direction.hash(&mut hasher);
+                dynamic_row.interval.hash(&mut hasher);
+                dynamic_row.tick_count.hash(&mut hasher);
+            }
+        }
+        self.player.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // Compact description of the exact board in the level format, for pasting into bug reports
+    pub fn fingerprint(&self) -> String {
+        let mut fingerprint = String::new();
+        for row in self.gameboard.iter().rev() {
+            fingerprint.push_str(&level_row_line(row.as_ref()));
+            fingerprint.push('\n');
+        }
+        fingerprint.push_str(&format!("player {} {}\n", self.player.0, self.player.1));
+        fingerprint
+    }
+
+    fn log_fingerprint(&self) -> io::Result<()> {
+        let Some(dir) = &self.config.config_dir else {
+            return Ok(());
+        };
+        fs::create_dir_all(dir)?;
+        let mut log = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("fingerprints.log"))?;
+        writeln!(log, "# tick {}\n{}", self.step_count, self.fingerprint())
+    }
+
+    fn log_slow_frame(&self, warning: &str) -> io::Result<()> {
+        let Some(dir) = &self.config.config_dir else {
+            return Ok(());
+        };
+        fs::create_dir_all(dir)?;
+        let mut log = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("slow_frames.log"))?;
+        writeln!(log, "tick {}: {}", self.step_count, warning)
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        Metrics {
+            score: self.player_score,
+            step_count: self.step_count,
+            rows_traversed: self.rows_traversed,
+            lives: self.lives,
+            game_over: self.game_over,
+            cause_of_death: self.cause_of_death,
+            seed: self.seed,
+            cars_dodged: self.stats.cars_dodged,
+            coins: self.stats.coins_collected,
+            combo: self.combo.min(MAX_COMBO),
+            difficulty_level: difficulty_params(self.player_score).level,
+            speed: self.speed(),
+            effects: self.effects.iter().map(|effect| effect.kind).collect(),
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            gameboard: self.gameboard.iter().map(|row| row.clone_row()).collect(),
+            player: self.player,
+            player_score: self.player_score,
+        }
+    }
+
+    pub fn restore_checkpoint(&mut self, checkpoint: Checkpoint) {
+        self.gameboard = checkpoint.gameboard;
+        self.player = checkpoint.player;
+        self.player_score = checkpoint.player_score;
+        self.previous_player_pos = None;
+    }
+
+    pub fn quick_save(&mut self, slot: usize) {
+        if slot < CHECKPOINT_SLOTS {
+            self.checkpoints[slot] = Some(self.checkpoint());
+        }
+    }
+
+    // Loading leaves the slot in place so the same spot can be retried repeatedly
+    pub fn quick_load(&mut self, slot: usize) -> bool {
+        match self.checkpoints.get(slot).cloned().flatten() {
+            Some(checkpoint) => {
+                self.restore_checkpoint(checkpoint);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn tick(&mut self, action: Action) {
+        self.tick_sync(action);
+    }
+
+    // One step of the game with no terminal or timing involved
+    pub fn tick_sync(&mut self, action: Action) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(action);
+        }
+        match action {
+            Action::Pause => {
+                self.paused = !self.paused;
+                return;
+            }
+            Action::Quit if self.paused => {
+                self.quit = true;
+                return;
+            }
+            _ if self.paused => return,
+            Action::Save(slot) => {
+                self.quick_save(slot);
+                return;
+            }
+            Action::Load(slot) => {
+                self.quick_load(slot);
+                return;
+            }
+            Action::Fingerprint => {
+                if let Err(err) = self.log_fingerprint() {
+                    eprintln!("Could not write fingerprint: {}", err);
+                }
+                return;
+            }
+            Action::Undo if self.config.turn_based => {
+                self.undo();
+                return;
+            }
+            Action::Undo => {
+                self.undo_move();
+                return;
+            }
+            Action::Hint => {
+                self.show_hint();
+                return;
+            }
+            Action::Move(_) | Action::Quit | Action::Idle => {}
+        }
+
+        if self.config.turn_based && action != Action::Idle {
+            self.push_undo();
+        }
+        self.step_count += 1;
+        self.stats.total_ticks += 1;
+        self.collision_flash = self.collision_flash.saturating_sub(1);
+        if self.step_count.is_multiple_of(SPEED_UP_STEPS) {
+            self.speed_up();
+        }
+        if self.step_count.is_multiple_of(SAFE_ROW_STEPS) && self.player_score > SAFE_ROW_MIN_SCORE
+        {
+            self.safe_row_owed = true;
+        }
+        if self.step_count.is_multiple_of(WIND_CHANGE_TICKS) {
+            self.wind_direction = self.rng.gen_range(-1..=1);
+            self.wind_timer = WIND_GUST_TICKS;
+        }
+        self.feedback = None;
+        let input = match action {
+            Action::Move(direction) => Some(direction),
+            _ => None,
+        };
+        if !self.has_effect(PowerUpKind::Freeze) {
+            self.gameboard.iter_mut().for_each(|row| {
+                row.tick();
+            });
+            self.apply_stream_drift();
+            self.move_npcs();
+        }
+        // a slide can't be steered or stopped: keys do nothing until the frog comes to rest
+        let sliding = self.is_sliding();
+        if sliding {
+            self.apply_slide();
+        }
+        let hops = if input == Some(Direction::Up) && self.has_effect(PowerUpKind::DoubleHop) {
+            2
+        } else {
+            1
+        };
+        let mut hopped = false;
+        if let Some(direction) = input.filter(|_| !sliding) {
+            for _ in 0..hops {
+                if !self.update_player(direction) {
+                    break;
+                }
+                hopped = true;
+                if let Some(road) = self
+                    .row(self.player.1 - 1)
+                    .filter(|row| row.kind() == RowKind::Road)
+                {
+                    self.stats.cars_dodged += road.get_base_row().object_count() as u32;
+                }
+                self.rows_traversed += 1;
+                if self.rows_traversed.is_multiple_of(SPEED_UP_EVERY) {
+                    self.speed_up();
+                }
+                self.player_score += self.combo.min(MAX_COMBO);
+                self.previous_move_points = self.combo.min(MAX_COMBO);
+                self.stats.rows_crossed += 1;
+                if let Some(time_remaining) = &mut self.time_remaining {
+                    *time_remaining += TIME_BONUS_PER_ROW;
+                }
+                self.stats.max_combo = self.stats.max_combo.max(self.combo.min(MAX_COMBO));
+                self.combo += 1;
+                self.combo_timeout = COMBO_TIMEOUT_TICKS;
+                if self.player.1 > self.scroll_row() {
+                    self.update_stack();
+                    self.player.1 -= 1;
+                }
+            }
+        }
+        if !hopped {
+            self.combo_timeout = self.combo_timeout.saturating_sub(1);
+            if self.combo_timeout == 0 {
+                self.combo = 1;
+            }
+        }
+        self.tick_effects();
+        self.tick_clock();
+        self.hint_ticks = self.hint_ticks.saturating_sub(1);
+        if self.hint_ticks == 0 {
+            self.hint.clear();
+        }
+        self.invincibility_ticks = self.invincibility_ticks.saturating_sub(1);
+        self.collect_pickup();
+        self.catch_npc();
+        if let Some(ticks) = self.config.auto_scroll_ticks {
+            if ticks > 0 && self.step_count.is_multiple_of(ticks) {
+                self.auto_scroll();
+            }
+        }
+        self.update_camera();
+        if self.level_complete() {
+            self.won = true;
+            self.game_over = true;
+        }
+        self.apply_wind();
+        // check the updated player position for legality
+        match self.check_player_collision() {
+            CollisionResult::OffBoard => self.fail(format!(
+                "player at {:?} is outside the {}x{} board",
+                self.player,
+                self.board_width(),
+                self.gameboard.len()
+            )),
+            _ if self.grace_ticks > 0 => self.grace_ticks -= 1,
+            _ if self.is_invincible() => {}
+            CollisionResult::HitByCar => self.lose_life(CauseOfDeath::HitByCar),
+            CollisionResult::Drowned
+                if self.water_drift_grace < WATER_DRIFT_GRACE
+                    && self.lethal_cause() == Some(CauseOfDeath::Drowned) => {}
+            CollisionResult::Drowned => self.lose_life(CauseOfDeath::Drowned),
+            CollisionResult::Safe | CollisionResult::BlockedByTree => {}
+        }
+        self.apply_river_current();
+        if let Some(replay) = &mut self.replay {
+            replay.frames.push(ReplayFrame {
+                tick: self.step_count,
+                input,
+                player: self.player,
+                score: self.player_score,
+            });
+        }
+        if self.trace.is_some() {
+            let board_hash = self.board_hash();
+            let (tick, player) = (self.step_count, self.player);
+            if let Some(trace) = &mut self.trace {
+                if let Err(err) = trace.record(tick, player, input, board_hash) {
+                    eprintln!("Could not write trace: {}", err);
+                    self.trace = None;
+                }
+            }
+        }
+    }
+
+    // A frog on a pad goes wherever the pad goes when its stream shifts. A pad wrapping round
+    // the edge would take the frog off the board, so it drowns instead.
+    pub fn apply_stream_drift(&mut self) {
+        let (column, row_index) = self.player;
+        let Some(row) = self.gameboard.get(row_index) else {
+            return;
+        };
+        match drifted_column(row.as_ref(), column, self.board_width()) {
+            Some(target) => self.player.0 = target,
+            None if self.grace_ticks == 0 && !self.is_invincible() => {
+                self.lose_life(CauseOfDeath::Drowned)
+            }
+            None => {}
+        }
+    }
+
+    // A frog in open water is swept a column downstream each tick, for WATER_DRIFT_GRACE ticks
+    // before it drowns, so a pad it is carried next to can still save it. Rocks and the board
+    // edge stop the drift.
+    pub fn apply_river_current(&mut self) {
+        if self.game_over || self.lethal_cause() != Some(CauseOfDeath::Drowned) {
+            self.water_drift_grace = 0;
+            return;
+        }
+        self.water_drift_grace = self.water_drift_grace.saturating_add(1);
+        let (column, row_index) = self.player;
+        let Some(direction) = self
+            .row(row_index)
+            .and_then(|row| row.dynamic_row())
+            .map(|moving| moving.direction)
+        else {
+            return;
+        };
+        let target = if direction {
+            column + 1
+        } else {
+            column.wrapping_sub(1)
+        };
+        if matches!(
+            self.cell(target, row_index),
+            Some(Cell::Passable | Cell::Lethal(_))
+        ) {
+            self.player.0 = target;
+        }
+    }
+
+    // Every WIND_GUST_TICKS while the wind blows, a frog on a water row is pushed a column
+    // downwind. Blown into open water it gets the same drift grace as a frog that hopped in;
+    // pinned against a rock or the board edge it loses a life.
+    fn apply_wind(&mut self) {
+        if self.wind_direction == 0 || self.game_over {
+            return;
+        }
+        self.wind_timer = self.wind_timer.saturating_sub(1);
+        if self.wind_timer > 0 {
+            return;
+        }
+        self.wind_timer = WIND_GUST_TICKS;
+        let (column, row_index) = self.player;
+        if self.row(row_index).map(|row| row.kind()) != Some(RowKind::Stream) {
+            return;
+        }
+        let target = column.wrapping_add_signed(self.wind_direction as isize);
+        if matches!(
+            self.cell(target, row_index),
+            Some(Cell::Passable | Cell::Lethal(_))
+        ) {
+            self.player.0 = target;
+        } else if self.grace_ticks == 0 && !self.is_invincible() {
+            self.lose_life(CauseOfDeath::Drowned);
+        }
+    }
+
+    pub fn wind_direction(&self) -> i8 {
+        self.wind_direction
+    }
+
+    pub fn lethal_cause(&self) -> Option<CauseOfDeath> {
+        match self.cell(self.player.0, self.player.1)? {
+            Cell::Lethal(cause) => Some(cause),
+            _ => None,
+        }
+    }
+
+    pub fn check_player_collision(&self) -> CollisionResult {
+        match self.cell(self.player.0, self.player.1) {
+            None => CollisionResult::OffBoard,
+            Some(Cell::Passable) => CollisionResult::Safe,
+            Some(Cell::Blocking) => CollisionResult::BlockedByTree,
+            Some(Cell::Lethal(CauseOfDeath::HitByCar)) => CollisionResult::HitByCar,
+            // no row reports falling off or the clock as a cell, so if one ever does it counts
+            // as drowning
+            Some(Cell::Lethal(
+                CauseOfDeath::Drowned | CauseOfDeath::FellOffBoard | CauseOfDeath::TimeExpired,
+            )) => CollisionResult::Drowned,
+        }
+    }
+
+    fn collect_pickup(&mut self) {
+        let (column, row) = self.player;
+        let Some(row) = self.gameboard.get_mut(row) else {
+            return;
+        };
+        match row.take_pickup(column) {
+            Some(CellContent::Coin) => {
+                self.player_score += COIN_VALUE;
+                self.stats.coins_collected += 1;
+            }
+            Some(CellContent::PowerUp(kind)) => {
+                self.stats.powerups_collected += 1;
+                self.apply_powerup(kind);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn npcs(&self) -> &[NpcFrog] {
+        &self.npcs
+    }
+
+    // Sometimes puts an NPC on a free cell of the new top row
+    fn spawn_npc(&mut self) {
+        if !self.rng.gen_bool(NPC_CHANCE) {
+            return;
+        }
+        let top = self.gameboard.len() - 1;
+        let free: Vec<usize> = (0..self.board_width())
+            .filter(|&column| self.cell(column, top) == Some(Cell::Passable))
+            .collect();
+        if let Some(&column) = free.choose(&mut self.rng) {
+            let npc = NpcFrog::new((column, top), &mut self.rng);
+            self.npcs.push(npc);
+        }
+    }
+
+    // NPCs ride their pads like the frog does, hop to a random free neighbour when their timer
+    // runs out, and are gone once they end up somewhere deadly
+    fn move_npcs(&mut self) {
+        let mut npcs = std::mem::take(&mut self.npcs);
+        npcs.retain_mut(|npc| {
+            let (column, row_index) = npc.position;
+            let drifted = self
+                .row(row_index)
+                .and_then(|row| drifted_column(row, column, self.board_width()));
+            drifted.map(|column| npc.position.0 = column).is_some()
+        });
+        for npc in &mut npcs {
+            npc.move_timer = npc.move_timer.saturating_sub(1);
+            if npc.move_timer > 0 {
+                continue;
+            }
+            npc.move_timer = self.rng.gen_range(5..=15);
+            let (column, row_index) = npc.position;
+            let free: Vec<(usize, usize)> = [
+                (column, row_index + 1),
+                (column, row_index.wrapping_sub(1)),
+                (column.wrapping_sub(1), row_index),
+                (column.wrapping_add(1), row_index),
+            ]
+            .into_iter()
+            .filter(|&(column, row_index)| self.cell(column, row_index) == Some(Cell::Passable))
+            .collect();
+            if let Some(&target) = free.choose(&mut self.rng) {
+                npc.position = target;
+            }
+        }
+        npcs.retain(|npc| self.cell(npc.position.0, npc.position.1) == Some(Cell::Passable));
+        self.npcs = npcs;
+    }
+
+    fn npc_at(&self, position: (usize, usize)) -> bool {
+        self.npcs.iter().any(|npc| npc.position == position)
+    }
+
+    fn catch_npc(&mut self) {
+        let player = self.player;
+        if self.npc_at(player) {
+            self.npcs.retain(|npc| npc.position != player);
+            self.player_score += NPC_BONUS;
+        }
+    }
+
+    pub fn effects(&self) -> &[ActiveEffect] {
+        &self.effects
+    }
+
+    pub fn has_effect(&self, kind: PowerUpKind) -> bool {
+        self.effects.iter().any(|effect| effect.kind == kind)
+    }
+
+    pub fn apply_powerup(&mut self, kind: PowerUpKind) {
+        let Some(duration) = kind.duration(self.tick_interval) else {
+            match kind {
+                PowerUpKind::ExtraLife => self.lives = self.lives.saturating_add(1),
+                PowerUpKind::Reroll => self.reroll_rows_ahead(),
+                _ => {}
+            }
+            return;
+        };
+        let rules = self.config.power_up_rules;
+        if let Some(active) = self.effects.iter_mut().find(|effect| effect.kind == kind) {
+            match rules.repeat {
+                RepeatPolicy::Refresh => {
+                    active.remaining_ticks = active.remaining_ticks.max(duration);
+                }
+                RepeatPolicy::Extend => active.remaining_ticks += duration,
+                RepeatPolicy::Ignore => {}
+            }
+            return;
+        }
+        if self.effects.len() >= rules.max_active {
+            match rules.at_capacity {
+                CapacityPolicy::EvictOldest if !self.effects.is_empty() => {
+                    self.effects.remove(0);
+                }
+                _ => return,
+            }
+        }
+        self.effects.push(ActiveEffect {
+            kind,
+            remaining_ticks: duration,
+        });
+    }
+
+    // Shortest way to the top row in ticks, found by playing copies of the rows forward one
+    // tick per step with the frog riding its pad, as in tick. Waiting a tick is allowed but
+    // leaves nothing in the path, so only the moves come back.
+    pub fn safe_path(&self) -> Option<Vec<Direction>> {
+        let steps = self.find_safe_path()?;
+        Some(steps.into_iter().map(|(_, direction)| direction).collect())
+    }
+
+    fn find_safe_path(&self) -> Option<HintPath> {
+        let width = self.board_width();
+        let top = self.gameboard.len() - 1;
+        if self.player.1 == top {
+            return Some(Vec::new());
+        }
+        let moves = [
+            Some(Direction::Up),
+            None,
+            Some(Direction::Left),
+            Some(Direction::Right),
+            Some(Direction::Down),
+        ];
+        let mut rows: Vec<Box<dyn RowType>> =
+            self.gameboard.iter().map(|row| row.clone_row()).collect();
+        // position -> the moves that got there, each with the cell it was made from
+        let mut frontier: BTreeMap<(usize, usize), HintPath> =
+            BTreeMap::from([(self.player, Vec::new())]);
+        for _ in 0..HINT_MAX_TICKS {
+            rows.iter_mut().for_each(|row| {
+                row.tick();
+            });
+            let mut next = BTreeMap::new();
+            for ((column, row_index), path) in frontier {
+                let Some(column) = drifted_column(rows[row_index].as_ref(), column, width) else {
+                    continue;
+                };
+                for direction in moves {
+                    let target = match direction {
+                        None => (column, row_index),
+                        Some(Direction::Up) => (column, row_index + 1),
+                        Some(Direction::Down) => (column, row_index.wrapping_sub(1)),
+                        Some(Direction::Left) => (column.wrapping_sub(1), row_index),
+                        Some(Direction::Right) => (column + 1, row_index),
+                    };
+                    let passable = rows
+                        .get(target.1)
+                        .is_some_and(|row| row.cell(target.0) == Some(Cell::Passable));
+                    if !passable || next.contains_key(&target) {
+                        continue;
+                    }
+                    let mut path = path.clone();
+                    if let Some(direction) = direction {
+                        path.push(((column, row_index), direction));
+                    }
+                    if target.1 == top {
+                        return Some(path);
+                    }
+                    next.insert(target, path);
+                }
+            }
+            if next.is_empty() {
+                return None;
+            }
+            frontier = next;
+        }
+        None
+    }
+
+    // Draws the safe path for HINT_TIME, at the cost of HINT_COST points
+    pub fn show_hint(&mut self) -> bool {
+        let Some(steps) = self.find_safe_path() else {
+            self.feedback = Some("No safe path right now");
+            return false;
+        };
+        self.player_score = self.player_score.saturating_sub(HINT_COST);
+        self.hint = steps;
+        self.hint_ticks = (HINT_TIME.as_millis() / self.tick_interval.as_millis().max(1)) as u32;
+        true
+    }
+
+    fn tick_effects(&mut self) {
+        for effect in &mut self.effects {
+            effect.remaining_ticks = effect.remaining_ticks.saturating_sub(1);
+        }
+        self.effects.retain(|effect| effect.remaining_ticks > 0);
+    }
+
+    // Regenerates every row above the frog's
+    fn reroll_rows_ahead(&mut self) {
+        let keep = (self.player.1 + 1).min(self.gameboard.len());
+        let height = self.gameboard.len();
+        self.gameboard.truncate(keep);
+        self.npcs.retain(|npc| npc.position.1 < keep);
+        while self.gameboard.len() < height {
+            let history: Vec<RowKind> = self.gameboard.iter().map(|row| row.kind()).collect();
+            let row = GameState::generate_row_with_constraints(
+                self.spawner.as_mut(),
+                &history,
+                GameState::is_fair_row,
+                MAX_ROW_RETRIES,
+                self.board_width,
+                &mut self.rng,
+            );
+            self.gameboard.push(row);
+        }
+    }
+
+    // Panics if the frog is off the board or any row holds contradictory contents;
+    // generation and movement should make both impossible
+    pub fn assert_invariants(&self) {
+        assert!(
+            self.game_over || self.cell(self.player.0, self.player.1).is_some(),
+            "player at {:?} is outside the {}x{} board",
+            self.player,
+            self.board_width(),
+            self.gameboard.len()
+        );
+        for row in &self.gameboard {
+            row.assert_invariants();
+        }
+    }
+
+    // Plays the moves one per tick, checking invariants after each, until they run out or the
+    // game ends
+    pub fn run_scripted(&mut self, moves: &[Direction]) -> Metrics {
+        for &direction in moves {
+            if self.game_over {
+                break;
+            }
+            self.tick_sync(Action::Move(direction));
+            self.assert_invariants();
+        }
+        self.metrics()
+    }
+
+    // One tick per input, no waiting between them, stopping early if the game ends
+    pub fn run_headless(&mut self, inputs: Vec<Action>) -> GameResult {
+        for action in inputs {
+            if self.game_over {
+                break;
+            }
+            self.tick_sync(action);
+        }
+        GameResult {
+            score: self.player_score,
+            ticks_survived: self.step_count,
+            cause_of_death: self.cause_of_death,
+        }
+    }
+
+    pub fn world_row(&self) -> u64 {
+        self.scrolled_rows + self.player.1 as u64
+    }
+
+    // The finish line is the first row after the level's rows, which start above the two
+    // rows of starting grass
+    pub fn level_complete(&self) -> bool {
+        self.rows_remaining() == Some(0)
+    }
+
+    // Hops left to the finish line; None outside level mode
+    pub fn rows_remaining(&self) -> Option<u64> {
+        let level = self.config.level?;
+        Some((2 + level.length as u64).saturating_sub(self.world_row()))
+    }
+
+    // The board moves on without the frog; at the bottom row it gets pushed off
+    fn auto_scroll(&mut self) {
+        self.update_stack();
+        if self.player.1 > 0 {
+            self.player.1 -= 1;
+        } else if !self.is_invincible() {
+            self.lose_life(CauseOfDeath::FellOffBoard);
+        }
+    }
+
+    // Something impossible happened; end the run cleanly instead of panicking mid-frame
+    fn fail(&mut self, message: String) {
+        eprintln!("internal error: {}", message);
+        self.internal_error = Some(message);
+        self.game_over = true;
+    }
+
+    // Final death always wins over respawning: losing the last life ends the game on the spot,
+    // wherever the frog is, and records the cause. Otherwise the frog respawns on the bottom row,
+    // near the middle, with the startup grace and invincibility re-armed.
+    pub fn lose_life(&mut self, cause: CauseOfDeath) {
+        match cause {
+            CauseOfDeath::HitByCar => self.stats.times_hit_by_car += 1,
+            CauseOfDeath::Drowned => self.stats.times_drowned += 1,
+            CauseOfDeath::FellOffBoard | CauseOfDeath::TimeExpired => {}
+        }
+        self.lives = self.lives.saturating_sub(1);
+        if self.config.collision_flash {
+            self.collision_flash = COLLISION_FLASH_FRAMES;
+            self.collision_at = Some(self.player);
+        }
+        if self.lives == 0 {
+            self.game_over = true;
+            self.cause_of_death = Some(cause);
+        } else {
+            self.player = (self.respawn_column(), 0);
+            self.previous_player_pos = None;
+            self.grace_ticks = self.config.startup_grace_ticks;
+            self.invincibility_ticks = self.config.invincibility_ticks;
+        }
+    }
+
+    fn tick_clock(&mut self) {
+        let Some(time_remaining) = &mut self.time_remaining else {
+            return;
+        };
+        *time_remaining = time_remaining.saturating_sub(self.tick_interval);
+        if time_remaining.is_zero() && !self.game_over {
+            self.game_over = true;
+            self.cause_of_death = Some(CauseOfDeath::TimeExpired);
+        }
+    }
+
+    // Mean difficulty_rating over every row on the board
+    pub fn average_board_difficulty(&self) -> f32 {
+        let total: u32 = self
+            .gameboard
+            .iter()
+            .map(|row| row.difficulty_rating() as u32)
+            .sum();
+        total as f32 / self.gameboard.len().max(1) as f32
+    }
+
+    pub fn row_history(&self) -> RowHistory {
+        self.row_history
+    }
+
+    pub fn time_remaining(&self) -> Option<Duration> {
+        self.time_remaining
+    }
+
+    pub fn is_invincible(&self) -> bool {
+        self.config.invincible
+            || self.invincibility_ticks > 0
+            || self.has_effect(PowerUpKind::Shield)
+    }
+
+    pub fn invincibility_ticks(&self) -> u8 {
+        self.invincibility_ticks
+    }
+
+    // The middle of the bottom row, or the passable cell closest to it
+    fn respawn_column(&self) -> usize {
+        let middle = self.board_width() / 2;
+        (0..self.board_width())
+            .filter(|&column| self.cell(column, 0) == Some(Cell::Passable))
+            .min_by_key(|&column| column.abs_diff(middle))
+            .unwrap_or(middle)
+    }
+
+    pub fn turn_snapshot(&self) -> TurnSnapshot {
+        TurnSnapshot {
+            rng: self.rng.clone(),
+            gameboard: self.gameboard.iter().map(|row| row.clone_row()).collect(),
+            spawner: self.spawner.clone_strategy(),
+            player: self.player,
+            player_score: self.player_score,
+            game_over: self.game_over,
+            collision_flash: self.collision_flash,
+            collision_at: self.collision_at,
+            step_count: self.step_count,
+            rows_traversed: self.rows_traversed,
+            camera_x: self.camera_x,
+            grace_ticks: self.grace_ticks,
+            lives: self.lives,
+            invincibility_ticks: self.invincibility_ticks,
+            cause_of_death: self.cause_of_death,
+            elapsed: self.elapsed,
+            slide: self.slide,
+            last_horizontal_direction: self.last_horizontal_direction,
+            feedback: self.feedback,
+            scrolled_rows: self.scrolled_rows,
+            won: self.won,
+            effects: self.effects.clone(),
+            npcs: self.npcs.clone(),
+            combo: self.combo,
+            combo_timeout: self.combo_timeout,
+            stats: self.stats.clone(),
+            tick_interval: self.tick_interval,
+            time_remaining: self.time_remaining,
+            row_history: self.row_history,
+            previous_player_pos: self.previous_player_pos,
+            previous_move_points: self.previous_move_points,
+            hint: self.hint.clone(),
+            hint_ticks: self.hint_ticks,
+            power_up_owed: self.power_up_owed,
+            consecutive_hard_rows: self.consecutive_hard_rows,
+            safe_row_owed: self.safe_row_owed,
+            water_drift_grace: self.water_drift_grace,
+            wind_direction: self.wind_direction,
+            wind_timer: self.wind_timer,
+        }
+    }
+
+    pub fn restore_turn_snapshot(&mut self, snapshot: TurnSnapshot) {
+        self.rng = snapshot.rng;
+        self.gameboard = snapshot.gameboard;
+        if let Some(spawner) = snapshot.spawner {
+            self.spawner = spawner;
+        }
+        self.player = snapshot.player;
+        self.player_score = snapshot.player_score;
+        self.game_over = snapshot.game_over;
+        self.collision_flash = snapshot.collision_flash;
+        self.collision_at = snapshot.collision_at;
+        self.step_count = snapshot.step_count;
+        self.rows_traversed = snapshot.rows_traversed;
+        self.camera_x = snapshot.camera_x;
+        self.grace_ticks = snapshot.grace_ticks;
+        self.lives = snapshot.lives;
+        self.invincibility_ticks = snapshot.invincibility_ticks;
+        self.cause_of_death = snapshot.cause_of_death;
+        self.elapsed = snapshot.elapsed;
+        self.slide = snapshot.slide;
+        self.last_horizontal_direction = snapshot.last_horizontal_direction;
+        self.feedback = snapshot.feedback;
+        self.scrolled_rows = snapshot.scrolled_rows;
+        self.won = snapshot.won;
+        self.effects = snapshot.effects;
+        self.npcs = snapshot.npcs;
+        self.combo = snapshot.combo;
+        self.combo_timeout = snapshot.combo_timeout;
+        self.stats = snapshot.stats;
+        self.tick_interval = snapshot.tick_interval;
+        self.time_remaining = snapshot.time_remaining;
+        self.row_history = snapshot.row_history;
+        self.previous_player_pos = snapshot.previous_player_pos;
+        self.previous_move_points = snapshot.previous_move_points;
+        self.hint = snapshot.hint;
+        self.hint_ticks = snapshot.hint_ticks;
+        self.power_up_owed = snapshot.power_up_owed;
+        self.consecutive_hard_rows = snapshot.consecutive_hard_rows;
+        self.safe_row_owed = snapshot.safe_row_owed;
+        self.water_drift_grace = snapshot.water_drift_grace;
+        self.wind_direction = snapshot.wind_direction;
+        self.wind_timer = snapshot.wind_timer;
+    }
+
+    // Each turn saves the state before the move and world tick, dropping the oldest past UNDO_DEPTH
+    fn push_undo(&mut self) {
+        if self.undo_stack.len() == UNDO_DEPTH {
+            self.undo_stack.pop_front();
+        }
+        let snapshot = self.turn_snapshot();
+        self.undo_stack.push_back(snapshot);
+    }
+
+    // Takes back the frog's last step, and the points a forward step scored. There is one undo
+    // per step, and none onto a cell that has turned deadly since.
+    pub fn undo_move(&mut self) -> bool {
+        let Some((column, row_index)) = self.previous_player_pos else {
+            return false;
+        };
+        if !self.config.board.allow_undo_move
+            || !matches!(self.cell(column, row_index), Some(Cell::Passable))
+        {
+            return false;
+        }
+        self.player = (column, row_index);
+        self.player_score = self.player_score.saturating_sub(self.previous_move_points);
+        self.previous_player_pos = None;
+        self.previous_move_points = 0;
+        self.slide = None;
+        true
+    }
+
+    pub fn undo(&mut self) -> bool {
+        match self.undo_stack.pop_back() {
+            Some(snapshot) => {
+                self.restore_turn_snapshot(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn update_stack(&mut self) {
+        self.gameboard.remove(0);
+        self.scrolled_rows += 1;
+        self.npcs.retain(|npc| npc.position.1 > 0);
+        self.npcs.iter_mut().for_each(|npc| npc.position.1 -= 1);
+        self.previous_player_pos = self
+            .previous_player_pos
+            .and_then(|(column, row_index)| Some((column, row_index.checked_sub(1)?)));
+        self.collision_at = self
+            .collision_at
+            .and_then(|(column, row_index)| Some((column, row_index.checked_sub(1)?)));
+        self.hint.retain(|&((_, row_index), _)| row_index > 0);
+        self.hint
+            .iter_mut()
+            .for_each(|((_, row_index), _)| *row_index -= 1);
+        self.spawner.set_score(self.player_score);
+        let history: Vec<RowKind> = self.gameboard.iter().map(|row| row.kind()).collect();
+        let retries = if self.config.validate_board_passability {
+            MAX_PASSABILITY_RETRIES
+        } else {
+            0
+        };
+        // levels and wrapped worlds have a fixed layout, only the endless board is evened out
+        let force_grass = (self.consecutive_hard_rows >= MAX_CONSECUTIVE_HARD_ROWS
+            || self.safe_row_owed)
+            && self.config.level.is_none()
+            && self.config.wrap_world.is_none();
+        for attempt in 0..=retries {
+            if attempt > 0 {
+                self.gameboard.pop();
+            }
+            let row = if force_grass {
+                GameState::create_row_of_kind(&self.config, RowKind::Grass, &mut self.rng)
+            } else {
+                GameState::generate_row_with_constraints(
+                    self.spawner.as_mut(),
+                    &history,
+                    GameState::is_fair_row,
+                    MAX_ROW_RETRIES,
+                    self.board_width,
+                    &mut self.rng,
+                )
+            };
+            self.gameboard.push(row);
+            if attempt == retries || self.is_board_passable() {
+                break;
+            }
+        }
+        if force_grass {
+            self.safe_row_owed = false;
+        }
+        if let Some(row) = self.gameboard.last() {
+            self.row_history.push(row.kind());
+            self.consecutive_hard_rows =
+                GameState::count_hard_row(self.consecutive_hard_rows, row.as_ref());
+        }
+        // a hard board is made up for with a power-up on the next grass row
+        if self.average_board_difficulty() > POWER_UP_GUARANTEE_DIFFICULTY {
+            self.power_up_owed = true;
+        }
+        if self.power_up_owed {
+            if let Some(row) = self.gameboard.last_mut() {
+                self.power_up_owed = !row.place_power_up(&mut self.rng);
+            }
+        }
+        self.spawn_npc();
+        if cfg!(debug_assertions) {
+            self.assert_invariants();
+        }
+    }
+
+    // Keys from `reader`, read the way this game's config asks for. Build the reader before
+    // the game so keys pressed while it sets up are kept.
+    pub fn terminal_input(&self, reader: KeyReader) -> Box<dyn InputSource> {
+        Box::new(TerminalInput::new(
+            reader,
+            self.key_map.clone(),
+            &self.config,
+        ))
+    }
+
+    pub async fn run(&mut self, mut input: Box<dyn InputSource>) {
+        self.watch_for_interrupt();
+        self.wait_for_ready(input.as_mut()).await;
+        loop {
+            if self.shutdown.load(Ordering::SeqCst) {
+                self.restore_terminal();
+                return;
+            }
+            let frame_start = Instant::now();
+            self.print_gameboard();
+            let rendered = Instant::now();
+            let Some(action) = input.next_input().await else {
+                self.save_results();
+                return;
+            };
+            let read = Instant::now();
+            self.tick(action).await;
+            if self.quit {
+                self.restore_terminal();
+                self.save_results();
+                return;
+            }
+            let timing = FrameTiming {
+                render: rendered - frame_start,
+                input: read - rendered,
+                update: read.elapsed(),
+            };
+            // waiting on a key is the point of turn-based mode, not a stall
+            if let (Some(budget), false) = (self.config.tick_budget, self.config.turn_based) {
+                if let Some(warning) = timing.slow_frame_warning(budget) {
+                    let _ = self.log_slow_frame(&warning);
+                }
+            }
+            if self.game_over {
+                self.finish().await;
+                return;
+            }
+            sleep(self.next_frame_interval()).await;
+        }
+    }
+
+    // Lets a controller play instead of the keyboard, drawn and paced like a normal game
+    pub async fn run_with_controller<C: Controller>(&mut self, mut controller: C) {
+        self.watch_for_interrupt();
+        loop {
+            if self.shutdown.load(Ordering::SeqCst) {
+                self.restore_terminal();
+                return;
+            }
+            self.print_gameboard();
+            let action = controller.next_action(self);
+            self.tick(action).await;
+            if self.game_over {
+                self.finish().await;
+                return;
+            }
+            sleep(self.next_frame_interval()).await;
+        }
+    }
+
+    // The game over screen, then the results are saved
+    async fn finish(&mut self) {
+        while self.collision_flash > 0 {
+            self.print_gameboard();
+            self.collision_flash -= 1;
+            sleep(Duration::from_millis(150)).await;
+        }
+        self.print_gameboard();
+        if self.won {
+            println!("You made it to the finish line!");
+        }
+        match self.cause_of_death {
+            Some(CauseOfDeath::HitByCar) => println!("Game over! Hit by a car."),
+            Some(CauseOfDeath::Drowned) => println!("Game over! Drowned."),
+            Some(CauseOfDeath::FellOffBoard) => println!("Game over! Fell off the board."),
+            Some(CauseOfDeath::TimeExpired) => println!("Game over! Out of time."),
+            None if self.won => {}
+            None => println!("Game over!"),
+        }
+        if let Some(message) = &self.internal_error {
+            println!("The game stopped because of an internal error: {}", message);
+        }
+        print!("{}", self.stats_summary());
+        self.save_results();
+    }
+
+    // How long to wait before the next frame, counting it as played time unless paused
+    fn next_frame_interval(&mut self) -> Duration {
+        let tick_interval = if self.has_effect(PowerUpKind::SpeedBoost) {
+            self.tick_interval / 2
+        } else {
+            self.tick_interval
+        };
+        let interval = warmup_interval(tick_interval, self.config.warmup, self.elapsed);
+        if !self.paused {
+            self.elapsed += interval;
+        }
+        interval
+    }
+
+    // Ctrl-C stops the game at the next frame instead of killing it mid-draw
+    fn watch_for_interrupt(&self) {
+        let shutdown = Arc::clone(&self.shutdown);
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                shutdown.store(true, Ordering::SeqCst);
+            }
+        });
+    }
+
+    async fn wait_for_ready(&mut self, input: &mut dyn InputSource) {
+        let Some(ready_timeout) = self.config.ready_timeout else {
+            return;
+        };
+        if self.config.turn_based || !input.interactive() {
+            return;
+        }
+        self.print_gameboard();
+        println!("Press any key to start");
+        input.wait_until_ready(ready_timeout).await;
+    }
+
+    pub fn save_replay(&self, path: &Path) -> io::Result<()> {
+        let Some(recorder) = &self.recorder else {
+            return Err(io::Error::other("this game is not being recorded"));
+        };
+        let recording = ReplayRecorder {
+            final_score: self.player_score,
+            ..recorder.clone()
+        };
+        fs::write(path, serde_json::to_string(&recording)?)
+    }
+
+    pub fn stats(&self) -> &SessionStats {
+        &self.stats
+    }
+
+    pub fn stats_summary(&self) -> String {
+        let stats = &self.stats;
+        [
+            ("Ticks played", stats.total_ticks.to_string()),
+            ("Rows crossed", stats.rows_crossed.to_string()),
+            ("Hit by cars", stats.times_hit_by_car.to_string()),
+            ("Drowned", stats.times_drowned.to_string()),
+            ("Blocked by trees", stats.trees_blocked_by.to_string()),
+            ("Power-ups collected", stats.powerups_collected.to_string()),
+            ("Coins collected", stats.coins_collected.to_string()),
+            ("Cars dodged", stats.cars_dodged.to_string()),
+            ("Best combo", format!("x{}", stats.max_combo.max(1))),
+        ]
+        .iter()
+        .map(|(label, value)| format!("{:<20}{}\n", label, value))
+        .collect()
+    }
+
+    fn save_results(&mut self) {
+        if let Some(path) = &self.config.export_board_path {
+            if let Err(err) = self.export_board(path) {
+                eprintln!("Could not export board: {}", err);
+            }
+        }
+        if let (Some(replay), Some(path)) = (&self.replay, &self.config.replay_path) {
+            if let Err(err) = replay.save_to(path) {
+                eprintln!("Could not save replay: {}", err);
+            }
+        }
+        if let Some(path) = &self.config.recording_path {
+            if let Err(err) = self.save_replay(path) {
+                eprintln!("Could not save recording: {}", err);
+            }
+        }
+        if let Some(dir) = &self.config.data_dir {
+            if let Err(err) = self.stats.append_to(&SessionStats::log_path(dir)) {
+                eprintln!("Could not log session stats: {}", err);
+            }
+        }
+        let policy = self.config.high_score_policy;
+        if self
+            .high_score_store
+            .scoreboard()
+            .is_new_high_score(self.player_score, policy)
+        {
+            println!("New high score!");
+        }
+        let achieved_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        let entry = ScoreEntry {
+            score: self.player_score,
+            ticks_survived: self.step_count,
+            achieved_at,
+        };
+        if let Err(err) = self.high_score_store.record(entry, self.config.tie_break) {
+            eprintln!("Could not save scores: {}", err);
+        }
+        if let Some(dir) = &self.config.config_dir {
+            let path = PersistentStats::path(dir);
+            let mut stats = PersistentStats::load_from(&path);
+            stats.record_run(self.step_count);
+            if let Err(err) = stats.save_to(&path) {
+                eprintln!("Could not save stats: {}", err);
+            }
+            println!(
+                "Longest streak: {} ticks | Games played: {}",
+                stats.longest_streak, stats.games_played
+            );
+        }
+    }
+
+    // Returns true when the frog moved forward a row
+    pub fn update_player(&mut self, direction: Direction) -> bool {
+        let previous = self.player;
+        let advanced = match direction {
+            Direction::Up if self.config.allow_up && self.player.1 + 1 < self.gameboard.len() => {
+                self.player.1 += 1;
+                true
+            }
+            Direction::Down if self.config.allow_down => {
+                if self.player.1 > 0 {
+                    self.player.1 -= 1;
+                } else {
+                    match self.config.bottom_edge {
+                        EdgeBehavior::Block => {}
+                        EdgeBehavior::Die => {
+                            self.lose_life(CauseOfDeath::FellOffBoard);
+                            return false;
+                        }
+                        EdgeBehavior::Nothing => return false,
+                    }
+                }
+                false
+            }
+            Direction::Left if self.config.allow_left => {
+                if self.player.0 > 0 {
+                    self.player.0 -= 1;
+                } else {
+                    self.wrap_player(self.player.0.wrapping_sub(1));
+                }
+                false
+            }
+            Direction::Right if self.config.allow_right => {
+                if self.player.0 + 1 < self.board_width() {
+                    self.player.0 += 1;
+                } else {
+                    self.wrap_player(self.player.0 + 1);
+                }
+                false
+            }
+            _ => false,
+        };
+        if self.check_player_collision() == CollisionResult::BlockedByTree {
+            self.player = previous;
+            self.slide = None;
+            self.stats.trees_blocked_by += 1;
+            return false;
+        }
+        // an NPC sitting on a pad leaves no room for the frog
+        if self.npc_at(self.player)
+            && self.row(self.player.1).map(|row| row.kind()) == Some(RowKind::Stream)
+        {
+            self.player = previous;
+            self.slide = None;
+            return false;
+        }
+        if advanced && self.config.safe_landing_assist && self.lethal_cause().is_some() {
+            self.player = previous;
+            self.feedback = Some("Not safe to hop there yet!");
+            return false;
+        }
+        if self.player != previous {
+            self.previous_player_pos = Some(previous);
+            self.previous_move_points = 0;
+            if let Direction::Left | Direction::Right = direction {
+                self.last_horizontal_direction = Some(direction);
+            }
+        }
+        // on ice a sideways move starts (or redirects) a slide and hopping onto an ice row
+        // carries the frog on the way it last moved sideways; anything else ends it
+        self.slide = match direction {
+            _ if self.player == previous || !self.on_ice() => None,
+            Direction::Left | Direction::Right => Some(direction),
+            _ if self.player.1 != previous.1 => self.last_horizontal_direction,
+            _ => None,
+        };
+        advanced
+    }
+
+    // Maps a column just off the board back onto it: board_width (off the right side) becomes 0
+    // and usize::MAX (off the left side, from wrapping_sub) the last column
+    pub fn apply_horizontal_wrap(&self, new_col: usize) -> usize {
+        let width = self.board_width();
+        match new_col {
+            column if column < width => column,
+            column if column == width => 0,
+            _ => width - 1,
+        }
+    }
+
+    // Only moves the frog if wrapping is on and the cell it would wrap to is free
+    fn wrap_player(&mut self, new_col: usize) {
+        if !self.config.board.wrap_horizontal {
+            return;
+        }
+        let column = self.apply_horizontal_wrap(new_col);
+        if self.cell(column, self.player.1) == Some(Cell::Passable) {
+            self.player.0 = column;
+        }
+    }
+
+    pub fn is_sliding(&self) -> bool {
+        self.slide.is_some()
+    }
+
+    fn on_ice(&self) -> bool {
+        self.row(self.player.1)
+            .is_some_and(|row| row.kind() == RowKind::Ice)
+    }
+
+    // Carries the frog one cell further along its slide, stopping at walls and the board edge.
+    // Each slid cell goes through the normal collision check at the end of the tick.
+    fn apply_slide(&mut self) {
+        let Some(direction) = self.slide else {
+            return;
+        };
+        if !self.on_ice() {
+            self.slide = None;
+            return;
+        }
+        let column = match direction {
+            Direction::Left => self.player.0.checked_sub(1),
+            Direction::Right => Some(self.player.0 + 1),
+            _ => None,
+        };
+        match column {
+            Some(column)
+                if matches!(
+                    self.cell(column, self.player.1),
+                    Some(Cell::Passable | Cell::Lethal(_))
+                ) =>
+            {
+                self.player.0 = column;
+            }
+            _ => self.slide = None,
+        }
+    }
+}
+
+// Runs on every way out of the game, panics included
+impl Drop for GameState {
+    fn drop(&mut self) {
+        self.restore_terminal();
+    }
+}
+
+// Two games are equal when their boards, row timing and frog match, which is what two runs
+// from the same seed and inputs should end up with
+impl PartialEq for GameState {
+    fn eq(&self, other: &Self) -> bool {
+        self.board_hash() == other.board_hash()
+            && self.player_score == other.player_score
+            && self.lives == other.lives
+            && self.step_count == other.step_count
+            && self.game_over == other.game_over
+    }
+}
+
+// ASCII board plus a one-line status, handy for println! debugging and test assertions
+impl fmt::Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render_board_with(&Theme::ascii()))?;
+        write!(
+            f,
+            "score {} | lives {} | tick {} | player {},{}",
+            self.player_score, self.lives, self.step_count, self.player.0, self.player.1
+        )
+    }
+}
+
+// What to start once the options are read
+#[derive(Debug, PartialEq)]
+enum Launch {
+    Play,
+    Ai,
+    Level(PathBuf),
+}
+
+// Reads the play options, which can come in any order and combination, such as
+// `--theme night --board hard --zen`. A bad option comes back as its usage line.
+fn parse_options(args: &[String]) -> Result<(GameConfig, Launch), String> {
+    fn value<'a>(args: &mut impl Iterator<Item = &'a str>, usage: &str) -> Result<&'a str, String> {
+        args.next().ok_or_else(|| usage.to_string())
+    }
+    let mut config = GameConfig::default();
+    let mut launch = Launch::Play;
+    let mut args = args.iter().map(String::as_str).peekable();
+    while let Some(arg) = args.next() {
+        match arg {
+            "--record" => {
+                config.replay_path = Some(value(&mut args, "--record <path>")?.into());
+            }
+            "--record-inputs" => {
+                config.recording_path = Some(value(&mut args, "--record-inputs <path>")?.into());
+            }
+            "--trace" => config.trace_path = Some(value(&mut args, "--trace <path>")?.into()),
+            "--export-board" => {
+                config.export_board_path = Some(value(&mut args, "--export-board <path>")?.into());
+            }
+            // only what zen mode changes, so it combines with the other options
+            "--zen" => {
+                let zen = GameConfig::zen();
+                config.invincible = zen.invincible;
+                config.show_hud = zen.show_hud;
+                config.auto_scroll_ticks = zen.auto_scroll_ticks;
+            }
+            "--ai" => launch = Launch::Ai,
+            "--level" => launch = Launch::Level(value(&mut args, "--level <path>")?.into()),
+            "--theme" => {
+                let usage = "--theme <day|night|desert|snow>";
+                config.theme = ThemePreset::from_name(value(&mut args, usage)?)
+                    .ok_or_else(|| usage.to_string())?;
+            }
+            "--board" => {
+                let usage = "--board <easy|normal|hard>";
+                config.board = BoardConfig::from_name(value(&mut args, usage)?)
+                    .ok_or_else(|| usage.to_string())?;
+            }
+            "--timed" => {
+                let usage = "--timed <seconds>";
+                let seconds = value(&mut args, usage)?
+                    .parse()
+                    .map_err(|_| usage.to_string())?;
+                config.board.mode = GameMode::Timed {
+                    duration: Duration::from_secs(seconds),
+                };
+            }
+            "--wrap-horizontal" => config.board.wrap_horizontal = true,
+            // the pool size is optional
+            "--wrap" => {
+                let size = args.peek().and_then(|size| size.parse().ok());
+                if size.is_some() {
+                    args.next();
+                }
+                config.wrap_world = Some(size.unwrap_or(20));
+            }
+            other => return Err(format!("[options]: unknown option {}", other)),
+        }
+    }
+    Ok((config, launch))
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("diff-replays") if args.len() == 4 => {
+            let load = |path: &str| {
+                Replay::load_from(Path::new(path)).unwrap_or_else(|err| {
+                    eprintln!("Could not read replay {}: {}", path, err);
+                    std::process::exit(1);
+                })
+            };
+            let diff = diff_replays(&load(&args[2]), &load(&args[3]));
+            match diff.first_divergence {
+                Some(tick) => println!("Runs diverge at tick {}", tick),
+                None => println!("Runs never diverge"),
+            }
+            for (tick, delta) in diff.score_deltas {
+                println!("{}\t{:+}", tick, delta);
+            }
+            return;
+        }
+        Some("play-recording") if args.len() == 3 => {
+            let player = ReplayPlayer::load(Path::new(&args[2])).unwrap_or_else(|err| {
+                eprintln!("Could not read recording {}: {}", args[2], err);
+                std::process::exit(1);
+            });
+            let result = player.run();
+            println!(
+                "Replayed {} ticks, final score {}",
+                result.ticks_survived, result.score
+            );
+            return;
+        }
+        // fuzz [iterations]: the regression seeds first, then fresh random ones. A panic or
+        // broken invariant aborts the run right after the seed that caused it is printed.
+        Some("fuzz") => {
+            let iterations: u64 = args.get(2).and_then(|n| n.parse().ok()).unwrap_or(1000);
+            let regressions = fs::read_to_string(FUZZ_REGRESSIONS).unwrap_or_default();
+            let seeds = regressions
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .filter_map(|line| line.trim().parse::<u64>().ok())
+                .chain((0..iterations).map(|_| rand::thread_rng().gen()));
+            let mut cases = 0;
+            for seed in seeds {
+                eprintln!("fuzz seed {}", seed);
+                let (config, moves) = fuzz_case(seed);
+                let mut first = GameState::headless(config.clone());
+                let mut second = GameState::headless(config);
+                first.run_scripted(&moves);
+                second.run_scripted(&moves);
+                assert!(first == second, "two runs of fuzz seed {} diverged", seed);
+                cases += 1;
+            }
+            println!("{} cases passed", cases);
+            return;
+        }
+        Some("export-scores") if args.len() == 3 => {
+            let path = Path::new(&args[2]);
+            let Some(format) = ExportFormat::from_extension(path) else {
+                eprintln!("Export path must end in .csv or .json");
+                std::process::exit(1);
+            };
+            let scoreboard = config_dir()
+                .map(|dir| Scoreboard::load_from(&Scoreboard::path(&dir)))
+                .unwrap_or_default();
+            if let Err(err) = scoreboard.export(path, format) {
+                eprintln!("Could not export scores to {}: {}", args[2], err);
+                std::process::exit(1);
+            }
+            return;
+        }
+        _ => {}
+    }
+    let (config, launch) =
+        parse_options(args.get(1..).unwrap_or_default()).unwrap_or_else(|usage| {
+            eprintln!("Usage: crossy_roads {}", usage);
+            std::process::exit(1);
+        });
+    match launch {
+        Launch::Play => {
+            let reader = KeyReader::new();
+            let mut game_state = GameState::from_config(config).with_key_map(KeyMap::from_env());
+            let input = game_state.terminal_input(reader);
+            game_state.run(input).await;
+        }
+        Launch::Ai => {
+            let mut game_state = GameState::from_config(config);
+            let ai = AiController::default();
+            game_state.run_with_controller(ai).await;
+        }
+        Launch::Level(path) => {
+            let reader = KeyReader::new();
+            let mut game_state = GameState::from_level_file(&path, config)
+                .unwrap_or_else(|err| {
+                    eprintln!("Could not load level {}: {}", path.display(), err);
+                    std::process::exit(1);
+                })
+                .with_key_map(KeyMap::from_env());
+            let input = game_state.terminal_input(reader);
+            game_state.run(input).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::{
+        any, prop, prop_assert, prop_assert_eq, prop_oneof, proptest, Just, Strategy,
+    };
+
+    #[test]
+    fn stream_is_never_followed_by_stream() {
+        let mut rng = StdRng::seed_from_u64(230);
+        let history = RowHistory::of(&[RowKind::Grass, RowKind::Stream]);
+        for _ in 0..1000 {
+            assert_ne!(choose_row_kind(history, &mut rng), RowKind::Stream);
+        }
+        // and streams do come up otherwise, so the loop above is checking something
+        let history = RowHistory::of(&[RowKind::Grass]);
+        assert!((0..1000).any(|_| choose_row_kind(history, &mut rng) == RowKind::Stream));
+    }
+
+    #[test]
+    fn phase_staggers_rows_with_the_same_interval() {
+        let row = || BaseRow::new(vec![true, false, false, false], CAR, ROAD);
+        let mut early = DynamicRow::new(row(), true, 3, 1);
+        let mut late = DynamicRow::new(row(), true, 3, 0);
+        let mut early_shifts = Vec::new();
+        let mut late_shifts = Vec::new();
+        for tick in 1..=9 {
+            early.tick();
+            late.tick();
+            if early.shifted() {
+                early_shifts.push(tick);
+            }
+            if late.shifted() {
+                late_shifts.push(tick);
+            }
+        }
+        assert_eq!(early_shifts, [2, 5, 8]);
+        assert_eq!(late_shifts, [3, 6, 9]);
+    }
+
+    // The default settings, except that nothing is read from or written to the real config
+    // and data directories
+    fn offline() -> GameConfig {
+        GameConfig {
+            config_dir: None,
+            data_dir: None,
+            ..GameConfig::default()
+        }
+    }
+
+    // A headless game on a hand-written board, with no grace period or invincibility so every
+    // collision counts straight away
+    fn level(rows: &str) -> GameState {
+        let config = GameConfig {
+            seed: Some(7),
+            startup_grace_ticks: 0,
+            invincibility_ticks: 0,
+            ..offline()
+        };
+        GameState::from_level_str(rows, config)
+            .unwrap()
+            .with_renderer(StringRenderer::default())
+    }
+
+    #[test]
+    fn quick_load_restores_position_score_and_board() {
+        let mut state = level(
+            "grass - - ..............
+             road > 1:0 #.............
+             grass - - ..............
+             grass - - ..............
+             player 3 0",
+        );
+        state.tick_sync(Action::Move(Direction::Up));
+        state.tick_sync(Action::Save(0));
+        let saved = (state.player, state.player_score, state.fingerprint());
+        state.tick_sync(Action::Move(Direction::Right));
+        state.tick_sync(Action::Idle);
+        state.tick_sync(Action::Idle);
+        assert_ne!(
+            (state.player, state.fingerprint()),
+            (saved.0, saved.2.clone())
+        );
+        state.tick_sync(Action::Load(0));
+        assert_eq!(
+            (state.player, state.player_score, state.fingerprint()),
+            saved
+        );
+        // the slot is still there for another go
+        state.tick_sync(Action::Idle);
+        assert!(state.quick_load(0));
+        assert_eq!(state.fingerprint(), saved.2);
+    }
+
+    #[test]
+    fn theme_mixing_widths_needs_a_cell_width() {
+        let mixed = Theme::new(GRASS, TREE, ROAD, 'C', WATER, PAD, FROG);
+        assert!(matches!(
+            mixed,
+            Err(ThemeError::MixedWidths { glyph: 'C', .. })
+        ));
+        assert!(Theme::new('.', 'T', '_', 'C', '~', 'O', 'F').is_ok());
+        let padded = Theme {
+            car: 'C',
+            cell_width: Some(2),
+            ..Theme::default()
+        };
+        assert!(padded.validate().is_ok());
+        let cramped = Theme {
+            cell_width: Some(1),
+            ..Theme::default()
+        };
+        assert!(matches!(
+            cramped.validate(),
+            Err(ThemeError::TooWide { cell_width: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn traffic_light_stops_the_row_on_red() {
+        let mut road =
+            Road::new(vec![true, false, false, false], 1, true, 0).with_traffic_light(2, 3);
+        let mut colours = Vec::new();
+        for _ in 0..10 {
+            let before = road.dynamic_row.row.objects.clone();
+            road.dynamic_row.tick();
+            let red = road.dynamic_row.light.unwrap().is_red();
+            let moved = road.dynamic_row.row.objects != before;
+            assert_eq!(
+                moved,
+                !red,
+                "the row moved on a {} light",
+                if red { "red" } else { "green" }
+            );
+            assert_eq!(road.dynamic_row.shifted(), !red);
+            colours.push(red);
+        }
+        // green for three ticks, then red for two
+        assert_eq!(
+            colours,
+            [false, false, true, true, false, false, false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn screen_lines_count_down_from_the_top() {
+        assert_eq!(RowOrdering::BottomUp.screen_line(0, 7), Some(6));
+        assert_eq!(RowOrdering::BottomUp.screen_line(6, 7), Some(0));
+        assert_eq!(RowOrdering::BottomUp.screen_line(2, 7), Some(4));
+        assert_eq!(RowOrdering::BottomUp.screen_line(7, 7), None);
+        assert_eq!(RowOrdering::TopDown.screen_line(2, 7), Some(2));
+        assert_eq!(RowOrdering::TopDown.screen_line(7, 7), None);
+
+        let state = level(
+            "grass - - ..............
+             grass - - ..............
+             grass - - ..............
+             grass - - ..............
+             player 3 1",
+        );
+        assert_eq!(state.screen_line(state.player.1), Some(2));
+        let board = state.render_board();
+        let frog_line = board.lines().position(|line| line.contains(FROG));
+        assert_eq!(frog_line, Some(2));
+    }
+
+    // One of every row the level format can describe, for the round-trip tests
+    const EVERY_ROW: &str = "grass - - ..#...........
+         road < 2:1 #..##......... light:4:6:2:red
+         stream > 3:0 ##....##.@.... flowing
+         stream < 5:4 ...##.....##..
+         ice - - .....#........
+         road > 1:0 ....#.........
+         grass - - ..............
+         player 4 0";
+
+    #[test]
+    fn fingerprint_round_trips_through_the_level_parser() {
+        let state = level(EVERY_ROW);
+        let fingerprint = state.fingerprint();
+        let reparsed = level(&fingerprint);
+        assert_eq!(reparsed.fingerprint(), fingerprint);
+        assert_eq!(reparsed.snapshot(), state.snapshot());
+        assert!(fingerprint.contains(" flowing"));
+        assert!(fingerprint.contains(" light:4:6:2:red"));
+        assert!(fingerprint.contains('@'));
+
+        // a generated board too, part way through a run
+        let mut state = GameState::headless(GameConfig {
+            seed: Some(218),
+            ..offline()
+        });
+        for _ in 0..20 {
+            state.tick_sync(Action::Idle);
+        }
+        let reparsed = level(&state.fingerprint());
+        assert_eq!(reparsed.fingerprint(), state.fingerprint());
+        assert_eq!(reparsed.board_hash(), state.board_hash());
+    }
+
+    #[test]
+    fn maximum_interval_never_overflows_the_tick_counter() {
+        let row = BaseRow::new(vec![true, false, false], CAR, ROAD);
+        let mut row = DynamicRow::new(row, true, u8::MAX, u8::MAX - 1);
+        for _ in 0..10_000 {
+            row.tick();
+            assert!(row.tick_count < row.interval());
+        }
+        // the first shift comes a tick in, then every 255 ticks
+        assert_eq!(row.shifts(), 1 + (10_000 - 1) / u64::from(u8::MAX));
+    }
+
+    #[test]
+    #[should_panic(expected = "interval must be at least 1")]
+    fn zero_interval_is_refused() {
+        DynamicRow::new(BaseRow::new(vec![false; 3], CAR, ROAD), true, 0, 0);
+    }
+
+    #[test]
+    fn zero_interval_snapshot_is_rejected() {
+        let mut snapshot = level(EVERY_ROW).snapshot();
+        let motion = snapshot.rows[1].motion.as_mut().unwrap();
+        motion.interval = 0;
+        assert!(GameState::from_snapshot_with_config(snapshot, offline()).is_err());
+        let level = GameState::from_level_str("road > 0:0 ..#...", GameConfig::default());
+        assert!(level.is_err());
+    }
+
+    #[test]
+    fn snapshot_round_trips_every_row_and_the_board_config() {
+        let config = GameConfig {
+            board: BoardConfig {
+                object_density: 0.5,
+                wrap_horizontal: true,
+                mode: GameMode::Timed {
+                    duration: Duration::from_secs(90),
+                },
+                ..BoardConfig::hard()
+            },
+            ..offline()
+        };
+        let mut state = GameState::from_level_str(EVERY_ROW, config)
+            .unwrap()
+            .with_renderer(StringRenderer::default());
+        state.player_score = 12;
+        state.lives = 2;
+        let snapshot = state.snapshot();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let snapshot_from_json = serde_json::from_str(&json).unwrap();
+        let mut restored = GameState::from_snapshot_with_config(snapshot_from_json, offline())
+            .unwrap()
+            .with_renderer(StringRenderer::default());
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(restored.fingerprint(), state.fingerprint());
+        assert_eq!(restored.config.board, state.config.board);
+        assert!(restored.config.board.wrap_horizontal);
+
+        let rows = &snapshot.rows;
+        let light = rows[5].light.unwrap();
+        assert!(light.is_red());
+        assert_eq!(
+            (light.red_ticks, light.green_ticks, light.elapsed),
+            (4, 6, 2)
+        );
+        assert!(rows[4].flowing);
+        assert!(rows[4].rocks.as_ref().unwrap()[9]);
+        assert!(!rows[3].flowing && rows[3].rocks.is_none());
+        // a restored light and stream carry on exactly like the saved ones
+        for _ in 0..12 {
+            state.tick_sync(Action::Idle);
+            restored.tick_sync(Action::Idle);
+        }
+        assert_eq!(restored.snapshot(), state.snapshot());
+    }
+
+    fn vim_keys() -> KeyMap {
+        KeyMap {
+            up: vec![Key::Char('k')],
+            down: vec![Key::Char('j')],
+            left: vec![Key::Char('h')],
+            right: vec![Key::Char('l')],
+            hint: vec![Key::Char('?')],
+            ..KeyMap::default()
+        }
+    }
+
+    #[test]
+    fn custom_key_bindings_are_respected() {
+        let key_map = vim_keys();
+        assert_eq!(key_map.classify(&Key::Char('k')), Some(Direction::Up));
+        assert_eq!(key_map.classify(&Key::Char('h')), Some(Direction::Left));
+        assert_eq!(key_map.classify(&Key::Char('w')), None);
+        assert_eq!(key_map.classify(&Key::Char('z')), None);
+        assert_eq!(key_map.classify(&Key::Unknown), None);
+        assert_eq!(key_map.action(&Key::Char('?')), Some(Action::Hint));
+        assert_eq!(key_map.action(&Key::Char('2')), Some(Action::Save(1)));
+        assert_eq!(key_map.action(&Key::Char('#')), Some(Action::Load(2)));
+        assert_eq!(key_map.action(&Key::Escape), Some(Action::Pause));
+        assert_eq!(key_map.action(&Key::Char('z')), None);
+        // a key bound to a move and to something else does the something else
+        let clashing = KeyMap {
+            fingerprint: vec![Key::Char('k')],
+            ..vim_keys()
+        };
+        assert_eq!(clashing.action(&Key::Char('k')), Some(Action::Fingerprint));
+        assert_eq!(parse_key_name("esc"), Some(Key::Escape));
+        assert_eq!(parse_key_name(" x "), Some(Key::Char('x')));
+        assert_eq!(parse_key_name("f12"), None);
+    }
+
+    // Terminal input with these keys already pressed
+    fn pressed(keys: &[Key], config: &GameConfig) -> TerminalInput {
+        let (sender, receiver) = mpsc::channel(KEY_BUFFER);
+        for key in keys {
+            sender.try_send(key.clone()).unwrap();
+        }
+        let reader = KeyReader {
+            keys: receiver,
+            buffered: VecDeque::new(),
+        };
+        TerminalInput::new(reader, vim_keys(), config)
+    }
+
+    #[tokio::test]
+    async fn terminal_input_goes_through_the_key_map() {
+        let keys = [Key::Char('k'), Key::Char('w'), Key::Char('?')];
+        let mut input = pressed(&keys, &GameConfig::default());
+        let up = Some(Action::Move(Direction::Up));
+        assert_eq!(input.next_input().await, up);
+        assert_eq!(input.next_input().await, Some(Action::Idle));
+        assert_eq!(input.next_input().await, Some(Action::Hint));
+        assert_eq!(input.next_input().await, Some(Action::Idle));
+
+        let config = GameConfig {
+            resolve_multi_keys: true,
+            ..GameConfig::default()
+        };
+        let keys = [Key::Char('h'), Key::Char('k'), Key::Char('a')];
+        let mut input = pressed(&keys, &config);
+        assert_eq!(input.next_input().await, up);
+    }
+
+    #[test]
+    fn density_extremes_fill_nothing_or_everything() {
+        let mut rng = StdRng::seed_from_u64(278);
+        let empty = BaseRow::randomized_objects(14, CAR, ROAD, 0.0, &mut rng);
+        assert_eq!(empty.object_count(), 0);
+        assert_eq!(empty.density(), 0.0);
+        let full = BaseRow::randomized_objects(14, CAR, ROAD, 1.0, &mut rng);
+        assert_eq!(full.object_count(), 14);
+        assert_eq!(full.density(), 1.0);
+        let half = BaseRow::new(vec![true, false, true, false], CAR, ROAD);
+        assert_eq!(half.density(), 0.5);
+    }
+
+    #[test]
+    fn river_current_drifts_the_frog_then_drowns_it_after_the_grace() {
+        let mut state = level(
+            "grass - - ..............
+             stream > 9:0 ..............
+             grass - - ..............
+             player 3 0",
+        );
+        let lives = state.lives;
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player, (4, 1));
+        assert_eq!(state.lives, lives);
+        state.tick_sync(Action::Idle);
+        assert_eq!(state.player, (5, 1));
+        assert_eq!(state.lives, lives);
+        state.tick_sync(Action::Idle);
+        assert_eq!(state.lives, lives - 1);
+        assert_eq!(state.player.1, 0);
+        assert_eq!(state.water_drift_grace, 0);
+    }
+
+    #[test]
+    fn river_current_can_carry_the_frog_onto_a_pad() {
+        let mut state = level(
+            "grass - - ..............
+             stream > 9:0 .....#........
+             grass - - ..............
+             player 3 0",
+        );
+        let lives = state.lives;
+        state.tick_sync(Action::Move(Direction::Up));
+        state.tick_sync(Action::Idle);
+        assert_eq!(state.player, (5, 1));
+        for _ in 0..5 {
+            state.tick_sync(Action::Idle);
+        }
+        assert_eq!(state.player, (5, 1));
+        assert_eq!(state.lives, lives);
+        assert_eq!(state.water_drift_grace, 0);
+    }
+
+    #[test]
+    fn river_current_stops_at_a_rock() {
+        let mut state = level(
+            "grass - - ..............
+             stream > 9:0 .....@........
+             grass - - ..............
+             player 3 0",
+        );
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player, (4, 1));
+        state.tick_sync(Action::Idle);
+        assert_eq!(state.player, (4, 1));
+    }
+
+    #[test]
+    fn frog_slides_on_ice_until_it_hits_a_tree() {
+        let mut state = level(
+            "grass - - ..............
+             grass - - ..............
+             ice - - .........#....
+             grass - - ..............
+             player 3 1",
+        );
+        state.tick_sync(Action::Move(Direction::Right));
+        assert_eq!(state.player, (4, 1));
+        assert!(state.is_sliding());
+        // keys do nothing mid-slide
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player, (5, 1));
+        for _ in 0..5 {
+            state.tick_sync(Action::Idle);
+        }
+        assert_eq!(state.player, (8, 1));
+        assert!(!state.is_sliding());
+        // from where it stopped it can move off the ice again
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player, (8, 2));
+    }
+
+    #[test]
+    fn stepping_onto_ice_slides_the_last_way_the_frog_moved_sideways() {
+        let mut state = level(
+            "grass - - ..............
+             ice - - ..............
+             grass - - ..............
+             player 5 0",
+        );
+        state.tick_sync(Action::Move(Direction::Left));
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player, (4, 1));
+        assert_eq!(state.last_horizontal_direction, Some(Direction::Left));
+        assert!(state.is_sliding());
+        for _ in 0..10 {
+            state.tick_sync(Action::Idle);
+        }
+        // stopped by the board edge
+        assert_eq!(state.player, (0, 1));
+        assert!(!state.is_sliding());
+    }
+
+    // A frog on the pads in the middle row, with a gust due on the next tick
+    fn windy(stream: &str, column: usize, wind_direction: i8) -> GameState {
+        let mut state = level(&format!(
+            "grass - - ..............\n{}\ngrass - - ..............\nplayer {} 1",
+            stream, column
+        ));
+        state.wind_direction = wind_direction;
+        state.wind_timer = 1;
+        state
+    }
+
+    #[test]
+    fn wind_pushes_the_frog_along_the_pads() {
+        let mut state = windy("stream > 9:0 ...###........", 4, -1);
+        let lives = state.lives;
+        state.tick_sync(Action::Idle);
+        assert_eq!(state.player, (3, 1));
+        assert_eq!(state.wind_timer, WIND_GUST_TICKS);
+        // nothing more until the next gust
+        for _ in 1..WIND_GUST_TICKS {
+            state.tick_sync(Action::Idle);
+        }
+        assert_eq!(state.player, (3, 1));
+        assert_eq!(state.lives, lives);
+    }
+
+    #[test]
+    fn wind_into_open_water_gets_the_drift_grace() {
+        let mut state = windy("stream < 9:0 ...###........", 5, 1);
+        let lives = state.lives;
+        state.tick_sync(Action::Idle);
+        // blown off the pad into the water, then carried back onto it by the current
+        assert_eq!(state.player, (5, 1));
+        assert_eq!(state.lives, lives);
+        assert_eq!(state.water_drift_grace, 1);
+    }
+
+    #[test]
+    fn wind_against_the_edge_or_a_rock_costs_a_life() {
+        let mut state = windy("stream > 9:0 ...........###", 13, 1);
+        let lives = state.lives;
+        state.tick_sync(Action::Idle);
+        assert_eq!(state.lives, lives - 1);
+
+        let mut state = windy("stream > 9:0 ....@##.......", 5, -1);
+        state.tick_sync(Action::Idle);
+        assert_eq!(state.lives, lives - 1);
+
+        let mut state = windy("stream > 9:0 ....###.......", 5, 0);
+        state.tick_sync(Action::Idle);
+        assert_eq!((state.player, state.lives), ((5, 1), lives));
+    }
+
+    #[test]
+    fn wind_does_nothing_off_the_water() {
+        let mut state = windy("grass - - ..............", 13, 1);
+        state.tick_sync(Action::Idle);
+        assert_eq!(state.player, (13, 1));
+    }
+
+    // Lengths of the runs of objects in a row, counting a run across the wrap as one
+    fn runs(objects: &[bool]) -> Vec<usize> {
+        let Some(start) = objects.iter().position(|&obj| !obj) else {
+            return vec![objects.len()];
+        };
+        let mut runs = Vec::new();
+        let mut run = 0;
+        for column in start..start + objects.len() {
+            if objects[column % objects.len()] {
+                run += 1;
+            } else if run > 0 {
+                runs.push(run);
+                run = 0;
+            }
+        }
+        if run > 0 {
+            runs.push(run);
+        }
+        runs
+    }
+
+    #[test]
+    fn groups_keep_their_size_as_the_row_moves() {
+        let mut rng = StdRng::seed_from_u64(296);
+        for size in VEHICLE_LENGTH {
+            let row = BaseRow::randomized_objects_grouped(14, CAR, ROAD, size, 2, &mut rng);
+            assert_eq!(runs(&row.objects), [size, size]);
+            let mut row = DynamicRow::new(row, true, 1, 0);
+            for _ in 0..100 {
+                row.tick();
+                assert_eq!(runs(&row.row.objects), [size, size]);
+            }
+        }
+        // more groups than fit gets as many as do
+        let row = BaseRow::randomized_objects_grouped(8, CAR, ROAD, 3, 5, &mut rng);
+        assert_eq!(runs(&row.objects), [3, 3]);
+
+        // generated roads meet their minimum with whole vehicles, all the same length
+        for board in [
+            BoardConfig::easy(),
+            BoardConfig::default(),
+            BoardConfig::hard(),
+        ] {
+            for min_cars_per_road in [1, 2, 4, 7, 11] {
+                let config = GameConfig {
+                    min_cars_per_road,
+                    board: board.clone(),
+                    ..offline()
+                };
+                for _ in 0..20 {
+                    let mut row = GameState::create_row_of_kind(&config, RowKind::Road, &mut rng);
+                    let vehicles = runs(&row.get_base_row().objects);
+                    assert!(vehicles.iter().sum::<usize>() >= min_cars_per_road);
+                    assert!(vehicles.iter().all(|&length| length == vehicles[0]));
+                    for _ in 0..100 {
+                        row.tick();
+                        assert_eq!(runs(&row.get_base_row().objects), vehicles);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn streams_are_grouped_under_a_column_density_curve_too() {
+        fn left_heavy(column: usize, width: usize) -> f64 {
+            1.0 - column as f64 / width as f64
+        }
+        for curve in [left_heavy, |_, _| 0.0] {
+            let config = GameConfig {
+                column_density: Some(curve),
+                ..GameConfig::default()
+            };
+            let mut rng = StdRng::seed_from_u64(296);
+            for _ in 0..50 {
+                let row = GameState::create_row_of_kind(&config, RowKind::Stream, &mut rng);
+                let pads = runs(&row.get_base_row().objects);
+                assert_eq!(pads, [STREAM_GROUP_SIZE; STREAM_GROUPS]);
+            }
+        }
+    }
+
+    #[test]
+    fn high_scores_round_trip_through_the_config_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_dir = dir.path().join("crossy_roads");
+        let mut store = HighScoreStore::load(Some(&config_dir));
+        assert!(config_dir.is_dir());
+        assert_eq!(store.high_score(), None);
+        for (achieved_at, score) in [5, 40, 12, 7, 33, 1, 90, 18, 26, 3, 61, 44]
+            .into_iter()
+            .enumerate()
+        {
+            let entry = ScoreEntry {
+                score,
+                ticks_survived: 100,
+                achieved_at: achieved_at as u64,
+            };
+            store.record(entry, TieBreak::default()).unwrap();
+        }
+        let reloaded = HighScoreStore::load(Some(&config_dir));
+        assert_eq!(reloaded.scores(), [90, 61, 44, 40, 33, 26, 18, 12, 7, 5]);
+        assert_eq!(reloaded.scoreboard(), store.scoreboard());
+        assert_eq!(reloaded.high_score(), Some(90));
+    }
+
+    #[test]
+    fn a_finished_game_saves_only_to_the_dirs_it_was_given() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = GameState::headless(GameConfig {
+            seed: Some(256),
+            config_dir: Some(dir.path().join("config")),
+            data_dir: Some(dir.path().join("data")),
+            ..GameConfig::default()
+        });
+        state.player_score = 17;
+        state.save_results();
+        let scores = Scoreboard::load_from(&Scoreboard::path(&dir.path().join("config")));
+        assert_eq!(scores.high_score(), Some(17));
+        let sessions = fs::read_to_string(dir.path().join("data").join("sessions.jsonl")).unwrap();
+        assert_eq!(sessions.lines().count(), 1);
+
+        // with no dirs at all nothing is loaded or kept
+        let mut state = GameState::headless(GameConfig {
+            seed: Some(256),
+            ..offline()
+        });
+        assert_eq!(state.high_score_store.high_score(), None);
+        state.player_score = 17;
+        state.save_results();
+        assert_eq!(state.high_score_store.path, None);
+    }
+
+    // Spawns grass, road and stream over and over, whatever came before
+    #[derive(Debug, Default)]
+    struct CycleSpawn {
+        next: usize,
+    }
+
+    impl SpawnStrategy for CycleSpawn {
+        fn next_row(
+            &mut self,
+            _history: &[RowKind],
+            mut rng: &mut dyn RngCore,
+        ) -> Box<dyn RowType> {
+            let kind = [RowKind::Grass, RowKind::Road, RowKind::Stream][self.next % 3];
+            self.next += 1;
+            GameState::create_row_of_kind(&offline(), kind, &mut rng)
+        }
+
+        fn reset(&mut self, _rng: &mut dyn RngCore) {
+            self.next = 0;
+        }
+    }
+
+    #[test]
+    fn new_game_from_mid_game_starts_a_clean_board() {
+        let config = GameConfig {
+            seed: Some(288),
+            ..offline()
+        };
+        let fresh = GameState::headless(config.clone());
+        let mut state = GameState::headless(config);
+        for _ in 0..2 {
+            for direction in [Direction::Up, Direction::Left, Direction::Up, Direction::Up] {
+                state.tick_sync(Action::Move(direction));
+                state.tick_sync(Action::Idle);
+            }
+            state.quick_save(0);
+            assert!(state.step_count > 0);
+            state.new_game();
+            assert_eq!(state.player, (7, 0));
+            assert_eq!(state.player_score, 0);
+            assert_eq!(state.lives, 3);
+            assert_eq!(state.step_count, 0);
+            assert_eq!(state.combo, 1);
+            assert!(!state.game_over);
+            assert!(state.checkpoints.iter().all(Option::is_none));
+            assert_eq!(state.gameboard.len(), 7);
+            // the same seed gives the same first board as a newly built game
+            assert_eq!(state.fingerprint(), fresh.fingerprint());
+            assert_eq!(state.row_history, fresh.row_history);
+            assert!(state == fresh);
+        }
+    }
+
+    #[test]
+    fn new_game_keeps_an_installed_spawn_strategy() {
+        let mut state = GameState::headless(GameConfig {
+            seed: Some(288),
+            ..offline()
+        })
+        .with_spawn_strategy(CycleSpawn::default());
+        state.new_game();
+        let kinds: Vec<RowKind> = state.gameboard.iter().map(|row| row.kind()).collect();
+        // two starting grass rows, then the cycle from its beginning
+        assert_eq!(
+            kinds,
+            [
+                RowKind::Grass,
+                RowKind::Grass,
+                RowKind::Grass,
+                RowKind::Road,
+                RowKind::Stream,
+                RowKind::Grass,
+                RowKind::Road
+            ]
+        );
+        state.new_game();
+        let again: Vec<RowKind> = state.gameboard.iter().map(|row| row.kind()).collect();
+        assert_eq!(again, kinds);
+    }
+
+    // Everything undo has to put back, in a form that can be compared
+    fn turn_state(state: &GameState) -> String {
+        format!(
+            "{:?}",
+            (
+                state.fingerprint(),
+                state.player_score,
+                state.lives,
+                state.combo,
+                state.step_count,
+                state.world_row(),
+                state.rows_remaining(),
+                &state.npcs,
+                state.row_history,
+                &state.effects,
+                state.cause_of_death,
+                (&state.stats, state.rng.clone().gen::<u64>()),
+            )
+        )
+    }
+
+    #[test]
+    fn undo_after_a_turn_restores_the_exact_prior_state() {
+        for level in [
+            None,
+            Some(LevelConfig {
+                length: 30,
+                object_budget: 40,
+            }),
+        ] {
+            let mut state = GameState::headless(GameConfig {
+                seed: Some(206),
+                turn_based: true,
+                level,
+                ..offline()
+            });
+            // far enough up that the next hop scrolls the board
+            while state.scrolled_rows == 0 && !state.game_over {
+                let before = turn_state(&state);
+                state.tick_sync(Action::Move(Direction::Up));
+                let after = turn_state(&state);
+                state.tick_sync(Action::Undo);
+                assert_eq!(turn_state(&state), before);
+                // and the same move from there plays out the same way, new rows included
+                state.tick_sync(Action::Move(Direction::Up));
+                assert_eq!(turn_state(&state), after);
+            }
+            assert!(!state.game_over);
+            for _ in 0..3 {
+                state.tick_sync(Action::Undo);
+            }
+            assert_eq!(state.scrolled_rows, 0);
+        }
+    }
+
+    #[test]
+    fn frog_rides_a_flowing_stream_a_column_per_shift() {
+        let mut state = level(
+            "grass - - ..............
+             stream > 3:0 ...#.......... flowing
+             grass - - ..............
+             player 3 0",
+        );
+        state.tick_sync(Action::Move(Direction::Up));
+        let start = state.player;
+        let lives = state.lives;
+        for _ in 0..9 {
+            state.tick_sync(Action::Idle);
+        }
+        assert_eq!(start, (3, 1));
+        assert_eq!(state.player, (6, 1));
+        assert_eq!(state.lives, lives);
+
+        // carried into the edge the frog drowns
+        let mut state = level(
+            "grass - - ..............
+             stream < 2:0 #............. flowing
+             grass - - ..............
+             player 0 0",
+        );
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player, (0, 1));
+        state.tick_sync(Action::Idle);
+        state.tick_sync(Action::Idle);
+        assert_eq!(state.lives, lives - 1);
+    }
+
+    #[test]
+    fn generated_streams_flow_and_carry_the_frog() {
+        let config = offline();
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..50 {
+            let row = GameState::create_row_of_kind(&config, RowKind::Stream, &mut rng);
+            assert!(row.is_flowing());
+            let mut state = level(
+                "grass - - ..............
+                 grass - - ..............
+                 grass - - ..............
+                 player 0 0",
+            );
+            let Some(column) = (0..14).find(|&column| row.check_position(column) == Some(true))
+            else {
+                continue;
+            };
+            state.gameboard[1] = row;
+            state.player = (column, 1);
+            let direction = state.gameboard[1].dynamic_row().unwrap().direction;
+            let lives = state.lives;
+            for _ in 0..30 {
+                let (column, shifts) = (
+                    state.player.0,
+                    state.gameboard[1].dynamic_row().unwrap().shifts,
+                );
+                state.tick_sync(Action::Idle);
+                if state.lives != lives {
+                    // only a pad wrapping off the edge drowns a riding frog
+                    assert!(column == 0 || column == 13);
+                    break;
+                }
+                let expected = match state.gameboard[1].dynamic_row().unwrap().shifts - shifts {
+                    0 => column,
+                    _ if direction => column + 1,
+                    _ => column - 1,
+                };
+                assert_eq!(state.player, (expected, 1));
+            }
+        }
+    }
+
+    #[test]
+    fn timed_power_ups_expire_after_their_duration() {
+        // a speed boost lasts SPEED_BOOST_TIME of half-length ticks
+        let boost = PowerUpKind::SpeedBoost;
+        assert_eq!(boost.duration(Duration::from_millis(50)), Some(200));
+        assert_eq!(boost.duration(Duration::from_millis(40)), Some(250));
+        assert_eq!(PowerUpKind::ExtraLife.duration(TICK_INTERVAL), None);
+        for kind in [
+            PowerUpKind::Freeze,
+            PowerUpKind::DoubleHop,
+            PowerUpKind::Shield,
+            PowerUpKind::SpeedBoost,
+        ] {
+            let mut state = level(
+                "grass - - ..............
+                 grass - - ..............
+                 grass - - ..............
+                 player 4 0",
+            );
+            state.apply_powerup(kind);
+            let duration = kind.duration(state.tick_interval).unwrap();
+            assert_eq!(state.effects()[0].remaining_ticks, duration);
+            for _ in 1..duration {
+                state.tick_sync(Action::Idle);
+            }
+            assert!(state.has_effect(kind), "{:?} ended early", kind);
+            state.tick_sync(Action::Idle);
+            assert!(
+                !state.has_effect(kind),
+                "{:?} outlasted {} ticks",
+                kind,
+                duration
+            );
+        }
+    }
+
+    #[test]
+    fn river_crossing_rides_the_pads() {
+        // the frog boards the bottom pad as it comes round to the bank's only open column and
+        // rides it two cells to the still pad above, crossing on the seventh tick
+        let row = |objects: [bool; 4], interval| {
+            DynamicRow::new(
+                BaseRow::new(objects.to_vec(), PAD, WATER),
+                true,
+                interval,
+                0,
+            )
+        };
+        let river = [
+            row([true, false, false, false], 1),
+            row([false, false, true, false], u8::MAX),
+        ];
+        assert!(river_crossable(&river, &[0], 7));
+        assert!(!river_crossable(&river, &[0], 6));
+
+        // carried off the edge, there is no way across
+        let river = [
+            row([true, false, false, false], 1),
+            row([false, false, false, false], u8::MAX),
+        ];
+        assert!(!river_crossable(&river, &[0], 50));
+    }
+
+    #[test]
+    fn level_objects_stay_within_the_budget() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for width in [1, 2, 3, 14] {
+            for object_budget in [0, 1, 5, 100] {
+                let config = LevelConfig {
+                    length: 12,
+                    object_budget,
+                };
+                let level = Level::generate(config, width, &mut rng);
+                let total: usize = level
+                    .rows
+                    .iter()
+                    .filter(|row| row.kind() != RowKind::Train)
+                    .map(|row| row.get_base_row().object_count())
+                    .sum();
+                assert!(
+                    total <= object_budget,
+                    "{} objects over {}",
+                    total,
+                    object_budget
+                );
+                for row in &level.rows {
+                    assert!(row.get_base_row().objects.iter().any(|&obj| !obj));
+                }
+            }
+        }
+    }
+
+    fn options(line: &str) -> Result<(GameConfig, Launch), String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        parse_options(&args)
+    }
+
+    #[test]
+    fn options_combine_in_any_order() {
+        let (config, launch) = options("--theme night --board hard --zen --wrap --ai").unwrap();
+        assert_eq!(config.theme, ThemePreset::Night);
+        assert_eq!(config.board.width, BoardConfig::hard().width);
+        assert!(config.invincible && !config.show_hud);
+        assert_eq!(config.wrap_world, Some(20));
+        assert_eq!(launch, Launch::Ai);
+
+        let (config, launch) = options("--wrap 8 --level a.level --timed 30 --theme snow").unwrap();
+        assert_eq!(config.wrap_world, Some(8));
+        assert_eq!(launch, Launch::Level(PathBuf::from("a.level")));
+        assert!(
+            matches!(config.board.mode, GameMode::Timed { duration } if duration.as_secs() == 30)
+        );
+        assert_eq!(config.theme, ThemePreset::Snow);
+
+        assert!(options("--theme").is_err());
+        assert!(options("--theme dusk").is_err());
+        assert!(options("--board easy --speed 3").is_err());
+        assert_eq!(options("").unwrap().1, Launch::Play);
+    }
+
+    #[test]
+    fn each_theme_draws_with_its_own_glyphs() {
+        let themes = [
+            ("day", [GRASS, TREE, ROAD, CAR, WATER, PAD, FROG]),
+            ("night", ['🌑', '🌲', '🖤', '🚗', '🌊', '🔵', '🐸']),
+            ("desert", ['🟨', '🌵', '🟫', '🚙', '🟤', '🪨', '🐸']),
+            ("snow", ['⬜', '🎄', '⬛', '🚗', '🧊', '🧊', '🐸']),
+        ];
+        for (name, glyphs) in themes {
+            let (config, _) = options(&format!("--theme {}", name)).unwrap();
+            let state = GameState::from_level_str(
+                "stream > 9:0 #..
+                 road > 9:0 #..
+                 grass - - #..
+                 player 2 0",
+                GameConfig {
+                    config_dir: None,
+                    data_dir: None,
+                    ..config
+                },
+            )
+            .unwrap();
+            let theme = &state.theme;
+            let [grass, tree, road, car, water, pad, player] = glyphs;
+            assert_eq!(
+                [
+                    theme.grass,
+                    theme.tree,
+                    theme.road,
+                    theme.car,
+                    theme.water,
+                    theme.pad,
+                    theme.player
+                ],
+                glyphs,
+                "{}",
+                name
+            );
+            let board = state.render_board_with(theme);
+            for glyph in [grass, tree, road, car, water, pad, player] {
+                assert!(board.contains(glyph), "{} board lacks {}", name, glyph);
+            }
+        }
+    }
+
+    #[test]
+    fn random_play_never_panics_or_stops_the_game_with_an_error() {
+        let mut rng = StdRng::seed_from_u64(222);
+        for seed in 0..100 {
+            let (config, _) = fuzz_case(seed);
+            let mut state = GameState::headless(config);
+            for _ in 0..1000 {
+                let action = match rng.gen_range(0..6) {
+                    0 => Action::Idle,
+                    1 => Action::Move(Direction::Down),
+                    2 => Action::Move(Direction::Left),
+                    3 => Action::Move(Direction::Right),
+                    _ => Action::Move(Direction::Up),
+                };
+                state.tick_sync(action);
+                state.assert_invariants();
+                state.render_board_with(&Theme::ascii());
+                if state.game_over {
+                    break;
+                }
+            }
+            assert_eq!(state.internal_error, None, "fuzz seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn fuzz_regression_seeds_still_pass() {
+        let regressions = fs::read_to_string(FUZZ_REGRESSIONS).unwrap();
+        let seeds: Vec<u64> = regressions
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.trim().parse().ok())
+            .collect();
+        assert!(!seeds.is_empty());
+        for seed in seeds {
+            let (config, moves) = fuzz_case(seed);
+            GameState::headless(config).run_scripted(&moves);
+        }
+    }
+
+    fn direction() -> impl Strategy<Value = Direction> {
+        prop_oneof![
+            3 => Just(Direction::Up),
+            1 => Just(Direction::Down),
+            1 => Just(Direction::Left),
+            1 => Just(Direction::Right),
+        ]
+    }
+
+    // Random configs from fuzz_case with moves proptest can shrink. A failing case is kept in
+    // proptest-regressions/main.txt and replayed first on later runs.
+    proptest! {
+        #[test]
+        fn fuzzed_games_keep_their_invariants(
+            seed in any::<u64>(),
+            moves in prop::collection::vec(direction(), 0..300),
+        ) {
+            let (config, _) = fuzz_case(seed);
+            let mut state = GameState::headless(config);
+            state.run_scripted(&moves);
+            prop_assert_eq!(&state.internal_error, &None);
+        }
+    }
+
+    fn with_grace(rows: &str, startup_grace_ticks: u32) -> GameState {
+        let config = GameConfig {
+            seed: Some(7),
+            startup_grace_ticks,
+            invincibility_ticks: 0,
+            ..offline()
+        };
+        GameState::from_level_str(rows, config).unwrap()
+    }
+
+    #[test]
+    fn collisions_count_only_once_the_startup_grace_is_over() {
+        let mut state = with_grace(
+            "grass - - ..............
+             grass - - ..............
+             road > 99:0 ....#.........
+             player 4 0",
+            3,
+        );
+        let lives = state.lives;
+        for _ in 0..3 {
+            state.tick_sync(Action::Idle);
+            assert_eq!(state.lives, lives);
+        }
+        state.tick_sync(Action::Idle);
+        assert_eq!(state.lives, lives - 1);
+
+        // a pad carrying the frog off the edge doesn't drown it during the grace either
+        let mut state = with_grace(
+            "grass - - ..............
+             grass - - ..............
+             stream < 1:0 #............. flowing
+             player 0 0",
+            2,
+        );
+        state.tick_sync(Action::Idle);
+        assert_eq!((state.player, state.lives), ((0, 0), lives));
+        state.tick_sync(Action::Idle);
+        assert_eq!(state.lives, lives);
+        for _ in 0..WATER_DRIFT_GRACE + 1 {
+            state.tick_sync(Action::Idle);
+        }
+        assert_eq!(state.lives, lives - 1);
+    }
+
+    #[test]
+    fn mixed_vehicles_keep_one_look_each_and_still_collide() {
+        let cells = |road: &Road| -> Vec<(bool, Option<char>)> {
+            let objects = &road.dynamic_row.row.objects;
+            objects
+                .iter()
+                .copied()
+                .zip(road.vehicle_types.clone())
+                .collect()
+        };
+        let mut seen = HashSet::new();
+        let mut rng = StdRng::seed_from_u64(280);
+        for _ in 0..20 {
+            let objects: Vec<bool> = "###..###...##.".chars().map(|c| c == '#').collect();
+            let mut road = Road::new(objects, 1, rng.gen(), 0).with_mixed_vehicles(&mut rng);
+            for _ in 0..30 {
+                // a vehicle is in every occupied cell and none elsewhere, one look per vehicle
+                for (obj, look) in cells(&road) {
+                    assert_eq!(obj, look.is_some());
+                }
+                for vehicle in vehicle_cells(&road.dynamic_row.row.objects) {
+                    let looks: HashSet<_> = vehicle
+                        .iter()
+                        .map(|&column| road.vehicle_types[column])
+                        .collect();
+                    assert_eq!(looks.len(), 1);
+                }
+                let before = road.vehicle_types.clone();
+                road.tick();
+                // with interval 1 the whole row, looks included, moves a cell each tick
+                let mut expected = before;
+                if road.dynamic_row.direction {
+                    expected.rotate_right(1);
+                } else {
+                    expected.rotate_left(1);
+                }
+                assert_eq!(road.vehicle_types, expected);
+            }
+            seen.extend(road.vehicle_types.iter().flatten().copied());
+        }
+        assert_eq!(seen.len(), VEHICLES.len());
+
+        // each kind of vehicle is drawn as itself and runs the frog over
+        for look in VEHICLES {
+            let mut state = level(
+                "grass - - ..............
+                 road > 99:0 ..............
+                 grass - - ..............
+                 player 4 0",
+            );
+            let mut road = Road::new(vec![false; 14], 99, true, 0);
+            road.dynamic_row.row.objects[6] = true;
+            road.vehicle_types[6] = Some(look);
+            state.gameboard[1] = Box::new(road);
+            let line = state.render_board_with(&Theme::default());
+            assert!(line.contains(look), "{} not drawn", look);
+            state.player = (6, 1);
+            assert_eq!(state.check_player_collision(), CollisionResult::HitByCar);
+            state.player = (5, 1);
+            assert_eq!(state.check_player_collision(), CollisionResult::Safe);
+        }
+    }
+
+    // Offers nothing but hard rows: roads shifting every tick and streams with a single pad
+    struct HardSpawn;
+
+    impl SpawnStrategy for HardSpawn {
+        fn next_row(&mut self, _history: &[RowKind], rng: &mut dyn RngCore) -> Box<dyn RowType> {
+            let width = offline().board.width;
+            let mut objects = vec![false; width];
+            objects[rng.gen_range(0..width)] = true;
+            if rng.gen_bool(0.5) {
+                Box::new(Road::new(objects, 1, rng.gen_bool(0.5), 0))
+            } else {
+                Box::new(Stream::new(objects, 1, rng.gen_bool(0.5), 0).flowing())
+            }
+        }
+    }
+
+    fn longest_hard_streak(state: &GameState) -> usize {
+        let mut streak = 0;
+        let mut longest = 0;
+        for row in &state.gameboard {
+            streak = match row.kind() {
+                RowKind::Grass | RowKind::Ice => 0,
+                _ if GameState::is_hard_row(row.as_ref()) => streak + 1,
+                _ => streak,
+            };
+            longest = longest.max(streak);
+        }
+        longest
+    }
+
+    #[test]
+    fn hard_rows_never_run_past_the_cap_even_when_every_row_offered_is_hard() {
+        for seed in 0..20 {
+            let mut state = GameState::headless(GameConfig {
+                seed: Some(seed),
+                ..offline()
+            })
+            .with_spawn_strategy(HardSpawn);
+            state.new_game();
+            assert!(longest_hard_streak(&state) <= MAX_CONSECUTIVE_HARD_ROWS as usize);
+            assert!(state.consecutive_hard_rows() <= MAX_CONSECUTIVE_HARD_ROWS);
+            // the board scrolling on, as it does when the frog hops past the scroll row
+            for _ in 0..200 {
+                state.update_stack();
+                assert!(longest_hard_streak(&state) <= MAX_CONSECUTIVE_HARD_ROWS as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn finished_runs_update_the_persisted_streak_and_game_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = GameState::headless(GameConfig {
+            seed: Some(214),
+            config_dir: Some(dir.path().to_path_buf()),
+            data_dir: None,
+            invincible: true,
+            ..GameConfig::default()
+        });
+        let path = PersistentStats::path(dir.path());
+        assert_eq!(
+            PersistentStats::load_from(&path),
+            PersistentStats::default()
+        );
+        for (ticks, longest, played) in [(50, 50, 1), (20, 50, 2), (80, 80, 3)] {
+            state.new_game();
+            state.run_headless(vec![Action::Idle; ticks]);
+            assert_eq!(state.step_count, ticks as u64);
+            state.save_results();
+            let stats = PersistentStats::load_from(&path);
+            assert_eq!(
+                (stats.longest_streak, stats.games_played),
+                (longest, played)
+            );
+        }
+    }
+
+    #[test]
+    fn bench_scenario_is_the_same_for_a_seed() {
+        let script = bench_scenario(212, 1000);
+        assert_eq!(script, bench_scenario(212, 1000));
+        assert_ne!(script, bench_scenario(213, 1000));
+        assert!(script.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(script.iter().all(|&(tick, _)| tick < 1000));
+        // a shorter run is the start of the longer one
+        let short = bench_scenario(212, 500);
+        assert_eq!(short[..], script[..short.len()]);
+
+        // and it drives the game the same way each time
+        let play = || {
+            let mut state = GameState::headless(GameConfig {
+                seed: Some(212),
+                invincible: true,
+                ..offline()
+            });
+            let mut moves = script.iter().peekable();
+            for tick in 0..1000 {
+                let action = match moves.next_if(|&&(at, _)| at == tick) {
+                    Some(&(_, direction)) => Action::Move(direction),
+                    None => Action::Idle,
+                };
+                state.tick_sync(action);
+            }
+            (state.player, state.player_score, state.world_row())
+        };
+        assert_eq!(play(), play());
+    }
+
+    #[test]
+    fn ai_scores_ten_before_it_first_dies() {
+        for seed in 0..5 {
+            // no startup grace, so the first collision counts
+            let mut state = GameState::headless(GameConfig {
+                seed: Some(seed),
+                startup_grace_ticks: 0,
+                ..offline()
+            });
+            let lives = state.lives;
+            let mut ai = AiController::default();
+            for _ in 0..1000 {
+                if state.game_over {
+                    break;
+                }
+                let score = state.player_score;
+                let action = ai.next_action(&state);
+                state.tick_sync(action);
+                if state.lives < lives {
+                    assert!(score >= 10, "seed {} died on {} points", seed, score);
+                    break;
+                }
+            }
+            assert!(
+                state.player_score >= 10,
+                "seed {} only got {}",
+                seed,
+                state.player_score
+            );
+        }
+    }
+
+    #[test]
+    fn safe_path_is_the_shortest_way_up() {
+        let rows = "grass - - ..............
+                    road > 99:0 ...#..........
+                    grass - - ....#.........
+                    grass - - ..............
+                    player 4 0";
+        let mut state = level(rows);
+        // the tree means a step aside first, and the car rules out the left
+        let path = state.safe_path().unwrap();
+        assert_eq!(
+            path,
+            [
+                Direction::Right,
+                Direction::Up,
+                Direction::Up,
+                Direction::Up
+            ]
+        );
+        let lives = state.lives;
+        for &direction in &path {
+            state.tick_sync(Action::Move(direction));
+            assert_eq!(state.lives, lives);
+        }
+        assert_eq!(state.world_row(), 3);
+
+        // a wall of trees has no way through
+        let state = level(
+            "grass - - ..............
+             grass - - ##############
+             grass - - ..............
+             player 4 0",
+        );
+        assert_eq!(state.safe_path(), None);
+    }
+
+    // Counts how often the game hands the terminal back
+    struct RestoreCounter(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl Renderer for RestoreCounter {
+        fn render_frame(&mut self, _state: &GameState) -> io::Result<()> {
+            Ok(())
+        }
+        fn restore(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn dropping_a_game_restores_the_terminal_without_panicking() {
+        drop(GameState::headless(offline()));
+        drop(GameState::from_config(offline()));
+        let restores = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let state = GameState::headless(offline()).with_renderer(RestoreCounter(restores.clone()));
+        assert_eq!(restores.load(Ordering::SeqCst), 0);
+        drop(state);
+        assert_eq!(restores.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn undo_move_takes_back_one_step_and_its_points() {
+        let rows = "grass - - ..............
+                    grass - - ..............
+                    grass - - ..............
+                    grass - - ..............
+                    player 4 0";
+        let mut state = level(rows);
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!((state.player, state.player_score), ((4, 1), 1));
+        assert!(state.undo_move());
+        assert_eq!((state.player, state.player_score), ((4, 0), 0));
+        // only one undo per step
+        assert!(!state.undo_move());
+        assert_eq!(state.player, (4, 0));
+
+        // the combo carries on, so these score 2 and 3; the undo gives back the 3
+        state.tick_sync(Action::Move(Direction::Up));
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player_score, 5);
+        assert!(state.undo_move());
+        assert_eq!((state.player, state.player_score), ((4, 1), 2));
+        // a sideways step scored nothing, so gives nothing back
+        state.tick_sync(Action::Move(Direction::Right));
+        assert!(state.undo_move());
+        assert_eq!((state.player, state.player_score), ((4, 1), 2));
+
+        // not back onto a cell a car has since driven into
+        let mut state = level(
+            "grass - - ..............
+             grass - - ..............
+             road > 1:0 ...#..........
+             player 4 0",
+        );
+        state.tick_sync(Action::Move(Direction::Up));
+        assert!(!state.undo_move());
+        assert_eq!(state.player, (4, 1));
+
+        // and never on the hard board
+        let mut state = level(rows);
+        state.config.board.allow_undo_move = false;
+        state.tick_sync(Action::Move(Direction::Up));
+        assert!(!state.undo_move());
+    }
+
+    #[test]
+    fn board_difficulty_averages_the_rows_and_owes_a_power_up_past_six() {
+        let mut state = level(
+            "grass - - ..............
+             grass - - #.............
+             grass - - ..............
+             grass - - ..............
+             player 4 0",
+        );
+        assert_eq!(state.average_board_difficulty(), 0.0);
+        state.update_stack();
+        assert!(!state.power_up_owed);
+
+        // four cars a road rate 8 each
+        let mut state = level(
+            "road > 99:0 #.#.#.#.......
+             road > 99:0 .#.#.#.#......
+             road > 99:0 #.#.#.#.......
+             road > 99:0 .#.#.#.#......
+             road > 99:0 #.#.#.#.......
+             player 8 0",
+        )
+        .with_spawn_strategy(CycleSpawn::default());
+        state.config.validate_board_passability = false;
+        assert_eq!(state.average_board_difficulty(), 8.0);
+        // the row that comes in next is grass, and with the board still at 6.4 it gets the
+        // power-up
+        state.update_stack();
+        assert_eq!(state.average_board_difficulty(), 6.4);
+        let top = state.gameboard.last().unwrap();
+        assert_eq!(top.kind(), RowKind::Grass);
+        assert!((0..14).any(|column| matches!(top.content(column), CellContent::PowerUp(_))));
+        assert!(!state.power_up_owed);
+    }
+
+    fn assert_row_sequence_rules(kinds: &[RowKind]) {
+        for (index, pair) in kinds.windows(2).enumerate() {
+            assert!(
+                pair != [RowKind::Stream, RowKind::Stream],
+                "streams back to back at {}",
+                index
+            );
+        }
+        for (index, three) in kinds.windows(3).enumerate() {
+            assert!(
+                three != [RowKind::Road; 3],
+                "three roads in a row at {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn ten_thousand_rows_keep_to_the_sequence_rules() {
+        let mut rng = StdRng::seed_from_u64(282);
+        let mut history = RowHistory::default();
+        let mut kinds = Vec::new();
+        for _ in 0..10_000 {
+            let kind = choose_row_kind(history, &mut rng);
+            history.push(kind);
+            kinds.push(kind);
+        }
+        assert_row_sequence_rules(&kinds);
+        for kind in [RowKind::Grass, RowKind::Road, RowKind::Stream] {
+            assert!(kinds.contains(&kind));
+        }
+
+        // and so do whole rows from the spawner
+        let mut spawner = RandomSpawn::new(offline());
+        let mut kinds = Vec::new();
+        for _ in 0..10_000 {
+            let kind = spawner.next_row(&kinds, &mut rng).kind();
+            kinds.push(kind);
+        }
+        assert_row_sequence_rules(&kinds);
+    }
+
+    #[test]
+    fn horizontal_wrap_goes_both_ways_only_onto_free_cells_and_only_when_on() {
+        let rows = "grass - - ..............
+                    grass - - ..............
+                    grass - - ..............
+                    player 0 0";
+        let mut state = level(rows);
+        assert_eq!(state.apply_horizontal_wrap(usize::MAX), 13);
+        assert_eq!(state.apply_horizontal_wrap(14), 0);
+        assert_eq!(state.apply_horizontal_wrap(5), 5);
+
+        state.config.board.wrap_horizontal = true;
+        state.player = (0, 1);
+        state.tick_sync(Action::Move(Direction::Left));
+        assert_eq!(state.player, (13, 1));
+        state.tick_sync(Action::Move(Direction::Right));
+        assert_eq!(state.player, (0, 1));
+
+        // a tree on the far side blocks the wrap
+        let mut state = level(
+            "grass - - ..............
+             grass - - ..............
+             grass - - .............#
+             player 0 0",
+        );
+        state.config.board.wrap_horizontal = true;
+        state.tick_sync(Action::Move(Direction::Left));
+        assert_eq!(state.player, (0, 0));
+
+        // and without the flag the edge is a wall
+        let mut state = level(rows);
+        state.player = (13, 1);
+        state.tick_sync(Action::Move(Direction::Right));
+        assert_eq!(state.player, (13, 1));
+        state.player = (0, 1);
+        state.tick_sync(Action::Move(Direction::Left));
+        assert_eq!(state.player, (0, 1));
+    }
+
+    #[test]
+    fn timed_game_ends_when_the_clock_runs_out() {
+        assert_eq!(
+            GameState::new_timed(2).time_remaining,
+            Some(Duration::from_secs(2))
+        );
+        let timed = |seconds| {
+            let mut config = offline();
+            config.board.mode = GameMode::Timed {
+                duration: Duration::from_secs(seconds),
+            };
+            GameState::headless(config)
+        };
+        // a second is twenty ticks of 50ms
+        let mut state = timed(1);
+        assert_eq!(state.tick_interval, Duration::from_millis(50));
+        for _ in 0..19 {
+            state.tick_sync(Action::Idle);
+        }
+        assert!(!state.game_over);
+        assert_eq!(state.time_remaining, Some(Duration::from_millis(50)));
+        state.tick_sync(Action::Idle);
+        assert!(state.game_over);
+        assert_eq!(state.cause_of_death, Some(CauseOfDeath::TimeExpired));
+        assert_eq!(
+            state.run_headless(vec![Action::Idle]).cause_of_death,
+            Some(CauseOfDeath::TimeExpired)
+        );
+
+        // each row crossed buys time back
+        let mut state = timed(1);
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(
+            state.time_remaining,
+            Some(Duration::from_millis(950) + TIME_BONUS_PER_ROW)
+        );
+    }
+
+    #[test]
+    fn npcs_are_drawn_wander_and_pay_out_when_caught() {
+        let rows = "grass - - ..............
+                    stream > 99:0 ....#.........
+                    grass - - ..............
+                    grass - - ..............
+                    player 4 0";
+        let mut state = level(rows);
+        state.npcs.push(NpcFrog {
+            position: (8, 1),
+            move_timer: 1,
+        });
+        assert!(state.render_board_with(&Theme::default()).contains(NPC));
+
+        // its timer is up, so it hops to a free neighbour and waits 5 to 15 ticks for the next
+        state.tick_sync(Action::Idle);
+        let npc = state.npcs[0];
+        let (column, row_index) = npc.position;
+        assert_eq!(column.abs_diff(8) + row_index.abs_diff(1), 1);
+        assert_eq!(state.cell(column, row_index), Some(Cell::Passable));
+        assert!((5..=15).contains(&npc.move_timer));
+
+        // hopping onto one catches it for the bonus on top of the step's point
+        let mut state = level(rows);
+        state.npcs.push(NpcFrog {
+            position: (4, 1),
+            move_timer: 15,
+        });
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player, (4, 1));
+        assert_eq!(state.player_score, 1 + NPC_BONUS);
+        assert!(state.npcs.is_empty());
+
+        // but one sitting on a pad keeps the frog off it
+        let mut state = level(rows);
+        state.npcs.push(NpcFrog {
+            position: (4, 2),
+            move_timer: 15,
+        });
+        state.player = (4, 1);
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player, (4, 1));
+        assert_eq!(state.npcs.len(), 1);
+    }
+
+    #[test]
+    fn boards_with_no_way_up_are_not_passable() {
+        let passable = |rows: &str| level(rows).is_board_passable();
+        // a wall of trees
+        assert!(!passable(
+            "grass - - ..............
+             grass - - ##############
+             grass - - ..............
+             player 4 0"
+        ));
+        // a stream without a pad
+        assert!(!passable(
+            "grass - - ..............
+             stream > 9:0 ..............
+             grass - - ..............
+             player 4 0"
+        ));
+        // the only gap in the trees opens onto water, and the pad is out of reach
+        assert!(!passable(
+            "grass - - ..............
+             stream > 9:0 ...#..........
+             grass - - ##########.###
+             grass - - ..............
+             player 4 0"
+        ));
+        // with the pad over the gap there is a way
+        assert!(passable(
+            "grass - - ..............
+             stream > 9:0 ..........#...
+             grass - - ##########.###
+             grass - - ..............
+             player 4 0"
+        ));
+        // cars don't count, they move on
+        assert!(passable(
+            "grass - - ..............
+             road > 9:0 ##############
+             grass - - ..............
+             player 4 0"
+        ));
+    }
+
+    #[test]
+    fn stats_summary_lines_up_each_counter() {
+        let mut state = level(
+            "grass - - ..............
+             grass - - ....#.........
+             grass - - ..............
+             player 4 0",
+        );
+        // nothing played yet still shows a x1 combo
+        assert!(state.stats_summary().ends_with("Best combo          x1\n"));
+        state.tick_sync(Action::Move(Direction::Up));
+        state.tick_sync(Action::Move(Direction::Right));
+        state.tick_sync(Action::Move(Direction::Up));
+        state.stats.times_drowned = 2;
+        state.stats.coins_collected = 12;
+        assert_eq!(
+            state.stats_summary(),
+            "Ticks played        3\n\
+             Rows crossed        1\n\
+             Hit by cars         0\n\
+             Drowned             2\n\
+             Blocked by trees    1\n\
+             Power-ups collected 0\n\
+             Coins collected     12\n\
+             Cars dodged         0\n\
+             Best combo          x1\n"
+        );
+    }
+
+    // Nothing but empty grass, however far the frog goes
+    struct OpenGrass;
+
+    impl SpawnStrategy for OpenGrass {
+        fn next_row(&mut self, _: &[RowKind], _: &mut dyn RngCore) -> Box<dyn RowType> {
+            Box::new(Grass::new(vec![false; 14]))
+        }
+    }
+
+    fn open_field() -> GameState {
+        level(
+            "grass - - ..............
+             grass - - ..............
+             grass - - ..............
+             grass - - ..............
+             grass - - ..............
+             player 4 0",
+        )
+        .with_spawn_strategy(OpenGrass)
+    }
+
+    #[test]
+    fn tick_interval_shrinks_to_the_floor_and_stays() {
+        let mut state = GameState::headless(GameConfig {
+            seed: Some(270),
+            invincible: true,
+            ..offline()
+        });
+        assert_eq!(state.tick_interval(), Duration::from_millis(50));
+        // every SPEED_UP_STEPS ticks played speed the game up a notch
+        for _ in 0..SPEED_UP_STEPS {
+            state.tick_sync(Action::Idle);
+        }
+        assert_eq!(state.tick_interval(), Duration::from_millis(45));
+        let mut intervals = Vec::new();
+        for _ in 0..8 {
+            state.speed_up();
+            intervals.push(state.tick_interval().as_millis());
+        }
+        assert_eq!(intervals, [40, 35, 30, 25, 20, 15, 15, 15]);
+
+        // and so does every SPEED_UP_EVERY rows hopped
+        let mut state = open_field();
+        for _ in 0..SPEED_UP_EVERY {
+            state.tick_sync(Action::Move(Direction::Up));
+        }
+        assert_eq!(state.world_row(), SPEED_UP_EVERY as u64);
+        assert_eq!(state.tick_interval(), Duration::from_millis(45));
+    }
+
+    #[test]
+    fn a_recorded_run_replays_to_the_same_result() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.json");
+        let config = GameConfig {
+            seed: Some(268),
+            recording_path: Some(path.clone()),
+            ..offline()
+        };
+        let mut state = GameState::headless(config.clone());
+        let mut rng = StdRng::seed_from_u64(268);
+        let actions: Vec<Action> = (0..300)
+            .map(|_| match rng.gen_range(0..8) {
+                0..=2 => Action::Move(Direction::Up),
+                3 => Action::Move(Direction::Left),
+                4 => Action::Move(Direction::Right),
+                5 => Action::Move(Direction::Down),
+                _ => Action::Idle,
+            })
+            .collect();
+        let recorded = state.run_headless(actions);
+        assert!(recorded.score > 0);
+        state.save_replay(&path).unwrap();
+
+        let player = ReplayPlayer::load(&path).unwrap().with_config(config);
+        assert_eq!(player.recording.final_score, recorded.score);
+        assert_eq!(player.recording.ticks, recorded.ticks_survived);
+        let replayed = player.run();
+        assert_eq!(replayed.score, recorded.score);
+        assert_eq!(replayed.ticks_survived, recorded.ticks_survived);
+        assert_eq!(replayed.cause_of_death, recorded.cause_of_death);
+    }
+
+    #[test]
+    fn combo_builds_to_five_and_resets_after_ten_idle_ticks() {
+        let mut state = open_field();
+        let mut scores = Vec::new();
+        for _ in 0..6 {
+            state.tick_sync(Action::Move(Direction::Up));
+            scores.push(state.player_score);
+        }
+        // each hop is worth one more than the last, up to five
+        assert_eq!(scores, [1, 3, 6, 10, 15, 20]);
+        // nine idle ticks keep the combo going
+        for _ in 0..COMBO_TIMEOUT_TICKS - 1 {
+            state.tick_sync(Action::Idle);
+        }
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player_score, 25);
+        // ten end it
+        for _ in 0..COMBO_TIMEOUT_TICKS {
+            state.tick_sync(Action::Idle);
+        }
+        assert_eq!(state.combo, 1);
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player_score, 26);
+        // a sideways step neither scores nor keeps the combo alive
+        state.tick_sync(Action::Move(Direction::Right));
+        assert_eq!(state.player_score, 26);
+        assert_eq!(state.combo_timeout, COMBO_TIMEOUT_TICKS - 1);
+    }
+
+    #[test]
+    fn hard_board_has_its_size_and_out_of_range_lookups_are_none() {
+        for seed in 0..10 {
+            let mut state = GameState::headless(GameConfig {
+                seed: Some(seed),
+                board: BoardConfig::hard(),
+                ..offline()
+            });
+            for _ in 0..2 {
+                assert_eq!(state.gameboard.len(), 9);
+                assert_eq!(state.board_width(), 16);
+                for row in &state.gameboard {
+                    assert_eq!(row.get_base_row().objects.len(), 16);
+                    if let Some(moving) = row.dynamic_row().filter(|_| row.kind() == RowKind::Road)
+                    {
+                        assert!((1..=3).contains(&moving.interval()));
+                    }
+                    for column in 0..16 {
+                        assert!(row.check_position(column).is_some());
+                    }
+                    for column in [16, 17, 100, usize::MAX] {
+                        assert_eq!(row.check_position(column), None);
+                        assert_eq!(row.cell(column), None);
+                    }
+                }
+                assert_eq!(state.cell(0, 9), None);
+                assert_eq!(state.cell(usize::MAX, 0), None);
+                // and the same after the board has moved on a few rows
+                for _ in 0..5 {
+                    state.update_stack();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn string_renderer_draws_a_known_board() {
+        let mut state = level(
+            "grass - - ..#.
+             road > 99:0 .#..
+             stream < 99:0 #...
+             grass - - ....
+             player 1 0",
+        );
+        state.theme = Theme::ascii();
+        let mut renderer = StringRenderer::default();
+        renderer.render_frame(&state).unwrap();
+        // the border and HUD are only coloured on a terminal that takes colours
+        assert_eq!(
+            console::strip_ansi_codes(&renderer.buffer),
+            "┌────┐\n\
+             │..T.│\n\
+             │_C__│\n\
+             │O~~~│\n\
+             │.F..│\n\
+             └────┘\n\
+             Score: 0 | Best: 0 | Speed: 1\n\
+             FFF\n"
+        );
+        // drawing again replaces the frame rather than adding to it
+        let first = renderer.buffer.clone();
+        renderer.render_frame(&state).unwrap();
+        assert_eq!(renderer.buffer, first);
+    }
+
+    #[test]
+    fn no_life_is_lost_while_invincible_after_a_hit() {
+        let mut state = GameState::from_level_str(
+            "grass - - ..............
+             road > 99:0 ....#.........
+             grass - - ..............
+             player 4 0",
+            GameConfig {
+                seed: Some(259),
+                startup_grace_ticks: 0,
+                invincibility_ticks: 5,
+                ..offline()
+            },
+        )
+        .unwrap();
+        let lives = state.lives;
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.lives, lives - 1);
+        assert_eq!(state.player.1, 0);
+        assert!(state.is_invincible());
+        // straight back under the car: the frog lives through the rest of its five ticks of
+        // invincibility and is hit on the tick it runs out
+        state.player = (4, 1);
+        for _ in 0..4 {
+            state.tick_sync(Action::Idle);
+            assert_eq!(state.lives, lives - 1);
+        }
+        state.tick_sync(Action::Idle);
+        assert_eq!(state.lives, lives - 2);
+    }
+
+    #[test]
+    fn a_passing_train_fills_the_track_and_hits_the_frog() {
+        let mut train = TrainRow::new(14, 3);
+        let mut occupied = Vec::new();
+        for _ in 0..10 {
+            train.tick();
+            let cells: Vec<bool> = (0..14)
+                .map(|column| train.check_position(column).unwrap())
+                .collect();
+            // the whole track at once or none of it
+            assert!(cells.iter().all(|&cell| cell == cells[0]));
+            occupied.push(cells[0]);
+        }
+        // three dormant ticks, the train for TRAIN_ACTIVE_TICKS, then dormant again
+        assert_eq!(
+            occupied,
+            [false, false, false, true, true, false, false, false, true, true]
+        );
+
+        let mut state = level(
+            "grass - - ..............
+             grass - - ..............
+             grass - - ..............
+             player 4 0",
+        );
+        state.gameboard[1] = Box::new(TrainRow::new(14, 3));
+        state.player = (4, 1);
+        let lives = state.lives;
+        for _ in 0..3 {
+            state.tick_sync(Action::Idle);
+            assert_eq!(state.check_player_collision(), CollisionResult::Safe);
+        }
+        state.tick_sync(Action::Idle);
+        assert_eq!(state.lives, lives - 1);
+        state.player = (4, 1);
+        assert_eq!(state.check_player_collision(), CollisionResult::HitByCar);
+    }
+
+    fn action() -> impl Strategy<Value = Action> {
+        prop_oneof![
+            3 => direction().prop_map(Action::Move),
+            1 => Just(Action::Idle),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn headless_runs_only_score_going_forward(
+            seed in any::<u64>(),
+            actions in prop::collection::vec(action(), 0..300),
+        ) {
+            let config = GameConfig {
+                seed: Some(seed),
+                ..offline()
+            };
+            let mut state = GameState::headless(config.clone());
+            let mut ticks = 0;
+            for &action in &actions {
+                if state.game_over {
+                    break;
+                }
+                let (score, world_row, coins) =
+                    (state.player_score, state.world_row(), state.stats.coins_collected);
+                state.tick_sync(action);
+                ticks += 1;
+                prop_assert!(state.player_score >= score);
+                let forward = action == Action::Move(Direction::Up);
+                if !forward {
+                    // no row is gained, and only coins and caught NPCs score
+                    prop_assert!(state.world_row() <= world_row);
+                    let pickups = state.player_score - score;
+                    let coins = (state.stats.coins_collected - coins) * COIN_VALUE;
+                    prop_assert!(pickups >= coins);
+                    prop_assert_eq!((pickups - coins) % NPC_BONUS, 0);
+                } else if state.world_row() > world_row {
+                    prop_assert!(state.player_score > score);
+                }
+            }
+            // run_headless is the same loop
+            let result = GameState::headless(config).run_headless(actions);
+            prop_assert_eq!(result.score, state.player_score);
+            prop_assert_eq!(result.ticks_survived, ticks);
+            prop_assert_eq!(result.cause_of_death, state.cause_of_death);
+        }
+    }
+
+    #[test]
+    fn collision_result_names_what_is_under_the_frog() {
+        let mut state = level(EVERY_ROW);
+        let cases = [
+            ((4, 0), CollisionResult::Safe),
+            ((4, 1), CollisionResult::HitByCar),
+            ((3, 1), CollisionResult::Safe),
+            ((5, 2), CollisionResult::BlockedByTree),
+            ((0, 2), CollisionResult::Safe),
+            ((3, 3), CollisionResult::Safe),
+            ((0, 3), CollisionResult::Drowned),
+            ((9, 4), CollisionResult::BlockedByTree),
+            ((6, 4), CollisionResult::Safe),
+            ((2, 4), CollisionResult::Drowned),
+            ((0, 5), CollisionResult::HitByCar),
+            ((1, 5), CollisionResult::Safe),
+            ((2, 6), CollisionResult::BlockedByTree),
+            ((14, 0), CollisionResult::OffBoard),
+            ((0, 7), CollisionResult::OffBoard),
+        ];
+        for (player, expected) in cases {
+            state.player = player;
+            assert_eq!(state.check_player_collision(), expected, "at {:?}", player);
+        }
+    }
+
+    #[test]
+    fn a_row_shifts_exactly_once_every_interval() {
+        for interval in [1, 2, 3, 5, 7, u8::MAX] {
+            for times in [1, 2, 10] {
+                let mut row = DynamicRow::new(
+                    BaseRow::new(vec![true, false, false, false], CAR, ROAD),
+                    true,
+                    interval,
+                    0,
+                );
+                let mut shifted_at = Vec::new();
+                for tick in 1..=interval as u64 * times {
+                    row.tick();
+                    if row.shifted() {
+                        shifted_at.push(tick);
+                    }
+                }
+                let expected: Vec<u64> = (1..=times).map(|n| n * interval as u64).collect();
+                assert_eq!(shifted_at, expected, "interval {} x{}", interval, times);
+                assert_eq!(row.shifts, times);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn run_plays_a_script_like_ticking_by_hand() {
+        let rows = "grass - - ..............
+             road > 1:0 #.............
+             grass - - ..............
+             grass - - ..............
+             player 3 0";
+        let script = [
+            Action::Move(Direction::Right),
+            Action::Move(Direction::Up),
+            Action::Move(Direction::Up),
+            Action::Idle,
+            Action::Move(Direction::Left),
+        ];
+        let mut by_hand = level(rows);
+        for action in script {
+            by_hand.tick_sync(action);
+        }
+        let mut scripted = level(rows);
+        scripted.run(Box::new(ScriptedInput::new(script))).await;
+        assert!(!scripted.game_over);
+        assert_eq!(scripted.step_count, script.len() as u64);
+        assert_eq!(scripted.player, by_hand.player);
+        assert_eq!(scripted.player_score, by_hand.player_score);
+        assert_eq!(scripted.fingerprint(), by_hand.fingerprint());
+
+        // the run stops at the last life's hit, with the rest of the script unplayed
+        let mut hit = level(
+            "grass - - ..............
+             road > 1:0 ..#...........
+             grass - - ..............
+             player 3 0",
+        );
+        hit.lives = 1;
+        let script = [Action::Move(Direction::Up), Action::Idle, Action::Idle];
+        hit.run(Box::new(ScriptedInput::new(script))).await;
+        assert!(hit.game_over);
+        assert_eq!(hit.cause_of_death, Some(CauseOfDeath::HitByCar));
+        assert_eq!(hit.step_count, 1);
+    }
+
+    proptest! {
+        #[test]
+        fn same_seed_games_stay_in_step(
+            seed in any::<u64>(),
+            actions in prop::collection::vec(action(), 0..300),
+        ) {
+            let (config, _) = fuzz_case(seed);
+            let mut first = GameState::headless(config.clone());
+            let mut second = GameState::headless(config);
+            prop_assert_eq!(first.fingerprint(), second.fingerprint());
+            for action in actions {
+                first.tick_sync(action);
+                second.tick_sync(action);
+                prop_assert_eq!(first.fingerprint(), second.fingerprint());
+                prop_assert_eq!(
+                    (first.player_score, first.lives, first.game_over),
+                    (second.player_score, second.lives, second.game_over)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rows_remaining_counts_down_to_the_finish_line() {
+        let mut state = GameState::headless(GameConfig {
+            seed: Some(253),
+            invincible: true,
+            level: Some(LevelConfig {
+                length: 12,
+                object_budget: 0,
+            }),
+            ..offline()
+        });
+        assert_eq!(state.rows_remaining(), Some(14));
+        let mut remaining = 14;
+        while !state.game_over {
+            let before = state.world_row();
+            state.tick_sync(Action::Move(Direction::Up));
+            let climbed = state.world_row() - before;
+            assert_eq!(state.rows_remaining(), Some(remaining - climbed));
+            remaining -= climbed;
+        }
+        assert!(state.won);
+        assert_eq!(remaining, 0);
+        assert!(state.level_complete());
+
+        let endless = GameState::headless(offline());
+        assert_eq!(endless.rows_remaining(), None);
+    }
+
+    #[test]
+    fn moving_past_the_side_edges_stays_on_the_board() {
+        let mut state = open_field();
+        let last = state.board_width() - 1;
+        state.player = (last, 2);
+        for _ in 0..3 {
+            state.tick_sync(Action::Move(Direction::Right));
+            assert_eq!(state.player, (last, 2));
+        }
+        state.player = (0, 2);
+        for _ in 0..3 {
+            state.tick_sync(Action::Move(Direction::Left));
+            assert_eq!(state.player, (0, 2));
+        }
+        assert!(!state.game_over);
+        assert_eq!(state.internal_error, None);
+    }
+
+    #[test]
+    fn fading_objects_in_leaves_collisions_alone() {
+        let rows = "road > 2:0 ##............
+             stream < 3:0 ###....##.....
+             road < 1:0 .........#....
+             grass - - ..............
+             player 3 0";
+        let mut faded = GameState::from_level_str(
+            rows,
+            GameConfig {
+                seed: Some(7),
+                fade_in: true,
+                ..offline()
+            },
+        )
+        .unwrap();
+        let mut plain = level(rows);
+        let mut saw_fade = false;
+        for _ in 0..30 {
+            faded.tick_sync(Action::Idle);
+            plain.tick_sync(Action::Idle);
+            let cells = |state: &GameState| -> Vec<Option<bool>> {
+                state
+                    .gameboard
+                    .iter()
+                    .flat_map(|row| (0..=state.board_width()).map(|col| row.check_position(col)))
+                    .collect::<Vec<_>>()
+            };
+            let before = cells(&faded);
+            let board = faded.render_board();
+            saw_fade |= board.contains(FADE_GLYPHS);
+            assert_eq!(cells(&faded), before);
+            assert_eq!(before, cells(&plain));
+            assert_eq!(
+                faded.check_player_collision(),
+                plain.check_player_collision()
+            );
+        }
+        assert!(saw_fade);
+        assert!(!plain.render_board().contains(FADE_GLYPHS));
+    }
+
+    #[test]
+    fn freeze_and_double_hop_stack_and_repeats_follow_the_policy() {
+        let mut state = level(
+            "grass - - ..............
+             grass - - ..............
+             grass - - ..............
+             grass - - ..............
+             road > 1:0 #.............
+             player 4 0",
+        );
+        state.apply_powerup(PowerUpKind::Freeze);
+        state.apply_powerup(PowerUpKind::DoubleHop);
+        let road = state.gameboard[0].get_base_row().objects.clone();
+        state.tick_sync(Action::Move(Direction::Right));
+        // the road stands still while the frog steps along it
+        assert_eq!(state.gameboard[0].get_base_row().objects, road);
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player, (5, 2));
+        let remaining: Vec<(PowerUpKind, u32)> = state
+            .effects()
+            .iter()
+            .map(|effect| (effect.kind, effect.remaining_ticks))
+            .collect();
+        assert_eq!(
+            remaining,
+            [(PowerUpKind::Freeze, 58), (PowerUpKind::DoubleHop, 98)]
+        );
+
+        // collecting an active effect again only touches its own timer
+        for (repeat, freeze_left) in [
+            (RepeatPolicy::Refresh, 60),
+            (RepeatPolicy::Extend, 118),
+            (RepeatPolicy::Ignore, 58),
+        ] {
+            let mut again = level(
+                "grass - - ..............
+                 grass - - ..............
+                 player 4 0",
+            );
+            again.config.power_up_rules.repeat = repeat;
+            again.apply_powerup(PowerUpKind::Freeze);
+            again.apply_powerup(PowerUpKind::DoubleHop);
+            again.tick_sync(Action::Idle);
+            again.tick_sync(Action::Idle);
+            again.apply_powerup(PowerUpKind::Freeze);
+            assert_eq!(again.effects().len(), 2, "{:?}", repeat);
+            assert_eq!(
+                again.effects()[0].remaining_ticks,
+                freeze_left,
+                "{:?}",
+                repeat
+            );
+            assert_eq!(again.effects()[1].remaining_ticks, 98, "{:?}", repeat);
+        }
+
+        // a third kind past max_active pushes out the oldest, or is turned away
+        for (at_capacity, kept) in [
+            (
+                CapacityPolicy::EvictOldest,
+                [PowerUpKind::DoubleHop, PowerUpKind::Shield],
+            ),
+            (
+                CapacityPolicy::DropNew,
+                [PowerUpKind::Freeze, PowerUpKind::DoubleHop],
+            ),
+        ] {
+            state.effects.clear();
+            state.config.power_up_rules.at_capacity = at_capacity;
+            for kind in [
+                PowerUpKind::Freeze,
+                PowerUpKind::DoubleHop,
+                PowerUpKind::Shield,
+            ] {
+                state.apply_powerup(kind);
+            }
+            let kinds: Vec<PowerUpKind> =
+                state.effects().iter().map(|effect| effect.kind).collect();
+            assert_eq!(kinds, kept);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_key_pressed_before_run_is_played_on_the_first_tick() {
+        let mut state = open_field();
+        state.config.ready_timeout = Some(Duration::from_secs(30));
+        state.config.resolve_multi_keys = false;
+        // pressed after the game is built but before it runs; it both ends the wait to start
+        // and moves the frog
+        let input = pressed(
+            &[Key::Char('k'), Key::Escape, Key::Char('q')],
+            &state.config,
+        );
+        let started = Instant::now();
+        state.run(Box::new(input)).await;
+        assert!(started.elapsed() < Duration::from_secs(30));
+        assert!(state.quit);
+        assert_eq!(state.player, (4, 1));
+        assert_eq!(state.player_score, 1);
+    }
+
+    #[test]
+    fn difficulty_progress_runs_from_one_threshold_to_the_next() {
+        for (score, level, progress) in [
+            (0, 0, 0.0),
+            (5, 0, 0.25),
+            (19, 0, 0.95),
+            (20, 1, 0.0),
+            (35, 1, 0.5),
+            (60, 2, 0.2),
+            (175, 3, 0.75),
+            (200, 4, 1.0),
+            (5000, 4, 1.0),
+        ] {
+            let difficulty = difficulty_params(score);
+            assert_eq!(difficulty.level, level, "score {}", score);
+            assert!(
+                (difficulty.progress(score) - progress).abs() < 1e-9,
+                "score {} is {} of the way, not {}",
+                score,
+                difficulty.progress(score),
+                progress
+            );
+        }
+        assert_eq!(progress_bar(0.0, 4), "    ");
+        assert_eq!(progress_bar(0.5, 4), "██  ");
+        assert_eq!(progress_bar(0.5625, 4), "██▎ ");
+        assert_eq!(progress_bar(1.0, 4), "████");
+    }
+
+    #[test]
+    fn a_car_keeps_its_id_until_it_leaves_the_row() {
+        let mut row = DynamicRow::new(
+            BaseRow::new(vec![false, true, false, true, false, false], CAR, ROAD),
+            true,
+            1,
+            0,
+        );
+        assert_eq!(row.object_id(1), Some(1));
+        assert_eq!(row.object_id(3), Some(2));
+        for column in 2..4 {
+            row.update_row();
+            assert_eq!(row.object_id(column), Some(1));
+            assert_eq!(row.object_id(column + 2), Some(2));
+        }
+        // the car in front goes over the edge and what comes back round is a new car
+        row.update_row();
+        assert_eq!(row.object_id(4), Some(1));
+        assert_eq!(row.object_id(0), Some(3));
+        row.update_row();
+        assert_eq!(row.object_id(5), Some(1));
+        assert_eq!(row.object_id(1), Some(3));
+        row.update_row();
+        let ids: Vec<Option<u32>> = (0..6).map(|column| row.object_id(column)).collect();
+        assert_eq!(ids, [Some(4), None, Some(3), None, None, None]);
+
+        let mut left = DynamicRow::new(
+            BaseRow::new(vec![false, false, true, false], CAR, ROAD),
+            false,
+            1,
+            0,
+        );
+        left.update_row();
+        left.update_row();
+        assert_eq!(left.object_id(0), Some(1));
+        left.update_row();
+        assert_eq!(left.object_id(3), Some(2));
+    }
+
+    #[test]
+    fn wrapped_world_keeps_going_with_a_climbing_score() {
+        let pool = 5;
+        let mut state = GameState::headless(GameConfig {
+            seed: Some(245),
+            invincible: true,
+            wrap_world: Some(pool),
+            ..offline()
+        });
+        let mut score = state.player_score;
+        for _ in 0..400 {
+            let step = state
+                .safe_path()
+                .and_then(|path| path.first().copied())
+                .unwrap_or(Direction::Up);
+            state.tick_sync(Action::Move(step));
+            assert!(state.player_score >= score);
+            score = state.player_score;
+        }
+        assert!(!state.game_over);
+        assert!(
+            state.world_row() > 10 * pool as u64,
+            "only reached {}",
+            state.world_row()
+        );
+        assert!(score as u64 >= state.world_row());
+        // rows spawned after the first two lap round the same pool of kinds
+        let kinds: Vec<RowKind> = state.gameboard.iter().map(|row| row.kind()).collect();
+        for (i, kind) in kinds.iter().enumerate().skip(pool) {
+            assert_eq!(*kind, kinds[i - pool]);
+        }
+
+        let mut spawner = WrapAroundSpawn::new(offline(), pool);
+        let mut rng = StdRng::seed_from_u64(245);
+        let mut lap = Vec::new();
+        for _ in 0..pool {
+            lap.push(spawner.next_row(&[], &mut rng));
+        }
+        assert_eq!(spawner.laps(), 1);
+        let again: Vec<Box<dyn RowType>> =
+            (0..pool).map(|_| spawner.next_row(&[], &mut rng)).collect();
+        assert_eq!(spawner.laps(), 2);
+        let kinds = |rows: &[Box<dyn RowType>]| -> Vec<RowKind> {
+            rows.iter().map(|row| row.kind()).collect()
+        };
+        assert_eq!(kinds(&again), kinds(&lap));
+        // the same kinds come round again, with their objects drawn afresh
+        let objects = |rows: &[Box<dyn RowType>]| -> Vec<Vec<bool>> {
+            rows.iter()
+                .map(|row| row.get_base_row().objects.clone())
+                .collect()
+        };
+        assert_ne!(objects(&again), objects(&lap));
+    }
+
+    #[test]
+    fn a_custom_strategy_spawns_every_new_row() {
+        let game = || {
+            GameState::headless(GameConfig {
+                seed: Some(244),
+                validate_board_passability: false,
+                ..offline()
+            })
+            .with_spawn_strategy(CycleSpawn::default())
+        };
+        let mut state = game();
+        state.new_game();
+        // the starting board took the cycle up to a Road, so it carries on from a Stream
+        let mut spawned = Vec::new();
+        for _ in 0..9 {
+            state.update_stack();
+            spawned.push(state.gameboard.last().unwrap().kind());
+        }
+        assert_eq!(
+            spawned,
+            [RowKind::Stream, RowKind::Grass, RowKind::Road].repeat(3)
+        );
+        let mut same = game();
+        same.new_game();
+        for _ in 0..9 {
+            same.update_stack();
+        }
+        assert_eq!(same.fingerprint(), state.fingerprint());
+    }
+
+    #[tokio::test]
+    async fn over_budget_frames_are_logged_with_their_breakdown() {
+        // fixed phase times stand in for the clock
+        let timing = FrameTiming {
+            render: Duration::from_millis(30),
+            input: Duration::from_millis(5),
+            update: Duration::from_millis(20),
+        };
+        assert_eq!(timing.total(), Duration::from_millis(55));
+        assert_eq!(timing.slow_frame_warning(Duration::from_millis(55)), None);
+        assert_eq!(
+            timing
+                .slow_frame_warning(Duration::from_millis(50))
+                .as_deref(),
+            Some("slow frame: 55ms over a 50ms budget (render 30ms, input 5ms, update 20ms)")
+        );
+
+        // a budget no frame can meet sends every frame down the warning path
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = level(
+            "grass - - ..............
+             grass - - ..............
+             player 4 0",
+        );
+        state.config.config_dir = Some(dir.path().to_path_buf());
+        state.config.tick_budget = Some(Duration::ZERO);
+        state
+            .run(Box::new(ScriptedInput::new([Action::Idle; 3])))
+            .await;
+        let log = fs::read_to_string(dir.path().join("slow_frames.log")).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 3);
+        for (tick, line) in lines.iter().enumerate() {
+            assert!(
+                line.starts_with(&format!("tick {}: slow frame: ", tick + 1)),
+                "{}",
+                line
+            );
+            assert!(line.contains("(render "), "{}", line);
+        }
+    }
+
+    #[test]
+    fn no_grass_cell_holds_a_coin_and_an_obstacle() {
+        let mut rng = StdRng::seed_from_u64(242);
+        let config = offline();
+        for _ in 0..2000 {
+            let row = GameState::create_row_of_kind(&config, RowKind::Grass, &mut rng);
+            row.assert_invariants();
+            let mut power_ups = 0;
+            for column in 0..config.board.width {
+                let tree = row.check_position(column).unwrap();
+                match row.content(column) {
+                    CellContent::Obstacle => assert!(tree),
+                    CellContent::Coin | CellContent::Empty => assert!(!tree),
+                    CellContent::PowerUp(_) => {
+                        assert!(!tree);
+                        power_ups += 1;
+                    }
+                    CellContent::Rock => panic!("a rock on grass"),
+                }
+            }
+            assert!(power_ups <= 1);
+        }
+        // every cell a coin could go on gets one, and not one tree is covered
+        let trees: Vec<bool> = (0..14).map(|column| column % 3 == 0).collect();
+        let full = Grass::with_coins(trees.clone(), 1.0, &mut rng).with_power_ups(1.0, &mut rng);
+        assert_eq!(full.validate(), Ok(()));
+        for (column, &tree) in trees.iter().enumerate() {
+            assert_eq!(full.content(column) == CellContent::Obstacle, tree);
+        }
+
+        let mut conflicted = Grass::new(vec![true, false]);
+        conflicted.contents[0] = CellContent::Coin;
+        assert_eq!(
+            conflicted.validate(),
+            Err("grass column 0 holds Coin but its tree flag is true".to_string())
+        );
+    }
+
+    #[test]
+    fn scoreboard_round_trips_through_csv_and_json() {
+        let mut scoreboard = Scoreboard::default();
+        for (score, ticks_survived, achieved_at) in
+            [(12, 300, 1_700_000_000), (40, 90, 5), (0, 0, 0)]
+        {
+            scoreboard.record(
+                ScoreEntry {
+                    score,
+                    ticks_survived,
+                    achieved_at,
+                },
+                TieBreak::EarliestFirst,
+            );
+        }
+        scoreboard.record(
+            ScoreEntry {
+                score: u32::MAX,
+                ticks_survived: u64::MAX,
+                achieved_at: u64::MAX,
+            },
+            TieBreak::EarliestFirst,
+        );
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["scores.csv", "scores.json"] {
+            let path = dir.path().join(name);
+            let format = ExportFormat::from_extension(&path).unwrap();
+            for board in [&Scoreboard::default(), &scoreboard] {
+                board.export(&path, format).unwrap();
+                assert_eq!(
+                    &Scoreboard::import(&path, format).unwrap(),
+                    board,
+                    "{}",
+                    name
+                );
+            }
+        }
+        assert_eq!(
+            scoreboard.to_csv().lines().next(),
+            Some("score,ticks_survived,achieved_at")
+        );
+        assert_eq!(ExportFormat::from_extension(Path::new("scores.txt")), None);
+
+        // fields that need quoting come back as they went in
+        for field in ["plain", "a,b", "say \"hi\"", ""] {
+            let line = [csv_field(field), csv_field("x")].join(",");
+            assert_eq!(parse_csv_line(&line), [field, "x"]);
+        }
+        let bad = Scoreboard::from_csv("score,achieved_at,ticks_survived\n1,2,3\n");
+        assert_eq!(bad.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let bad = Scoreboard::from_csv("score,ticks_survived,achieved_at\n-1,2,3\n");
+        assert_eq!(bad.unwrap_err().to_string(), "line 2: bad score");
+    }
+
+    #[tokio::test]
+    async fn the_world_waits_for_a_slow_first_key() {
+        let rows = "grass - - ..............
+             road > 1:0 ##............
+             grass - - ..............
+             player 4 0";
+        let mut state = level(rows);
+        state.config.ready_timeout = Some(Duration::from_secs(30));
+        state.config.resolve_multi_keys = false;
+        let (sender, receiver) = mpsc::channel(KEY_BUFFER);
+        let reader = KeyReader {
+            keys: receiver,
+            buffered: VecDeque::new(),
+        };
+        let input = TerminalInput::new(reader, vim_keys(), &state.config);
+        let delay = Duration::from_millis(300);
+        tokio::spawn(async move {
+            sleep(delay).await;
+            // the first key only starts the game, then the frog hops once and the player quits
+            for key in [Key::Char('x'), Key::Char('k'), Key::Escape, Key::Char('q')] {
+                sender.try_send(key).unwrap();
+            }
+        });
+        let started = Instant::now();
+        state.run(Box::new(input)).await;
+        assert!(started.elapsed() >= delay);
+        assert!(state.quit);
+        let mut expected = level(rows);
+        expected.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.step_count, 1);
+        assert_eq!(state.fingerprint(), expected.fingerprint());
+    }
+
+    #[test]
+    fn a_bouncing_row_turns_at_each_edge_and_keeps_its_objects() {
+        let cells = |pattern: &str| -> Vec<bool> { pattern.chars().map(|c| c == '#').collect() };
+        let mut row = DynamicRow::new(BaseRow::new(cells(".##..."), CAR, ROAD), true, 1, 0)
+            .with_traffic_mode(TrafficMode::Bounce);
+        let mut seen = Vec::new();
+        for _ in 0..8 {
+            row.update_row();
+            let pattern: String = row
+                .row
+                .objects
+                .iter()
+                .map(|&obj| if obj { '#' } else { '.' })
+                .collect();
+            seen.push((pattern, row.direction));
+        }
+        let expected = [
+            ("..##..", true),
+            ("...##.", true),
+            ("....##", true),
+            ("...##.", false),
+            ("..##..", false),
+            (".##...", false),
+            ("##....", false),
+            (".##...", true),
+        ];
+        let expected: Vec<(String, bool)> = expected
+            .iter()
+            .map(|&(pattern, direction)| (pattern.to_string(), direction))
+            .collect();
+        assert_eq!(seen, expected);
+        // nothing ever goes over the edge, so the cars are the same two all along
+        assert_eq!(row.object_id(1), Some(1));
+        assert_eq!(row.object_id(2), Some(2));
+
+        let mut rng = StdRng::seed_from_u64(238);
+        for _ in 0..200 {
+            let objects: Vec<bool> = (0..14).map(|_| rng.gen_bool(0.4)).collect();
+            let count = objects.iter().filter(|&&obj| obj).count();
+            let mut row = DynamicRow::new(BaseRow::new(objects, CAR, ROAD), rng.gen(), 1, 0)
+                .with_traffic_mode(TrafficMode::Bounce);
+            let ids: Vec<u32> = (0..14).filter_map(|column| row.object_id(column)).collect();
+            for _ in 0..40 {
+                row.update_row();
+                assert_eq!(row.row.object_count(), count);
+                let mut now: Vec<u32> =
+                    (0..14).filter_map(|column| row.object_id(column)).collect();
+                now.sort();
+                assert_eq!(now, ids);
+            }
+        }
+    }
+
+    #[test]
+    fn safe_landing_assist_only_refuses_lethal_forward_hops() {
+        let rows = "grass - - ..............
+             stream > 99:0 .....#........
+             road > 99:0 ....#.........
+             grass - - ..............
+             player 4 0";
+        let mut state = level(rows);
+        state.config.safe_landing_assist = true;
+        let lives = state.lives;
+        // onto the car and into the water: both refused, with a word why
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player, (4, 0));
+        assert_eq!(state.feedback, Some("Not safe to hop there yet!"));
+        state.player = (6, 1);
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.player, (6, 1));
+        // safe cells are hopped onto as usual
+        state.player = (5, 0);
+        for row in 1..=3 {
+            state.tick_sync(Action::Move(Direction::Up));
+            assert_eq!((state.player.0, state.world_row()), (5, row));
+        }
+        assert_eq!(state.lives, lives);
+
+        // without the assist the frog hops onto the car, or into the water for the current
+        // to take it
+        let mut unassisted = level(rows);
+        unassisted.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(unassisted.lives, lives - 1);
+        let mut unassisted = level(rows);
+        unassisted.player = (6, 1);
+        unassisted.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(unassisted.world_row(), 2);
+        assert_eq!(
+            unassisted.check_player_collision(),
+            CollisionResult::Drowned
+        );
+    }
+
+    #[test]
+    fn game_state_displays_its_board_and_status() {
+        let mut state = level(
+            "grass - - ..#.
+             road > 99:0 .#..
+             stream < 99:0 #...
+             grass - - ....
+             player 1 0",
+        );
+        // the display is the same whatever theme the game is drawn with
+        state.theme = Theme::preset(ThemePreset::Night);
+        assert_eq!(
+            state.to_string(),
+            "..T.\n\
+             _C__\n\
+             O~~~\n\
+             .F..\n\
+             score 0 | lives 3 | tick 0 | player 1,0"
+        );
+        state.tick_sync(Action::Move(Direction::Left));
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(
+            state.to_string(),
+            "..T.\n\
+             _C__\n\
+             F~~~\n\
+             ....\n\
+             score 1 | lives 3 | tick 2 | player 0,1"
+        );
+    }
+
+    #[test]
+    fn a_random_start_column_is_in_the_span_and_fixed_by_the_seed() {
+        let start = |seed: u64| {
+            let state = GameState::headless(GameConfig {
+                seed: Some(seed),
+                random_start_column: true,
+                ..offline()
+            });
+            assert_eq!(state.cell(state.player.0, 0), Some(Cell::Passable));
+            assert_eq!(state.cell(state.player.0, 1), Some(Cell::Passable));
+            state.player
+        };
+        let mut columns = HashSet::new();
+        for seed in 0..200 {
+            let (column, row) = start(seed);
+            assert_eq!(row, 0);
+            assert!(
+                START_SPAN.contains(&column),
+                "seed {} started at {}",
+                seed,
+                column
+            );
+            assert_eq!(start(seed), (column, 0));
+            columns.insert(column);
+        }
+        // every column of the span comes up for some seed
+        assert_eq!(columns, START_SPAN.collect());
+        // a board narrower than the span starts on its last column instead
+        let narrow = GameState::headless(GameConfig {
+            seed: Some(235),
+            random_start_column: true,
+            board: BoardConfig {
+                width: 4,
+                ..BoardConfig::default()
+            },
+            ..offline()
+        });
+        assert_eq!(narrow.player, (3, 0));
+    }
+
+    #[test]
+    fn zen_mode_never_ends_and_hides_the_score() {
+        let mut state = GameState::headless(GameConfig {
+            seed: Some(234),
+            config_dir: None,
+            data_dir: None,
+            ..GameConfig::zen()
+        });
+        let lives = state.lives;
+        let mut rng = StdRng::seed_from_u64(234);
+        let actions = [
+            Action::Idle,
+            Action::Move(Direction::Up),
+            Action::Move(Direction::Down),
+            Action::Move(Direction::Left),
+            Action::Move(Direction::Right),
+        ];
+        for _ in 0..3000 {
+            state.tick_sync(actions[rng.gen_range(0..actions.len())]);
+            assert!(!state.game_over);
+        }
+        assert_eq!(state.lives, lives);
+        assert_eq!(state.cause_of_death, None);
+        let mut renderer = StringRenderer::default();
+        renderer.render_frame(&state).unwrap();
+        assert!(!renderer.buffer.contains("Score:"));
+        assert!(!state.current_hud().show_stats);
+    }
+
+    #[test]
+    fn each_bottom_edge_behavior_does_its_own_thing() {
+        let rows = "grass - - ..............
+             grass - - ..............
+             grass - - ..............
+             player 2 0";
+        for behavior in [EdgeBehavior::Block, EdgeBehavior::Nothing] {
+            let mut state = level(rows);
+            state.config.bottom_edge = behavior;
+            let before = turn_state(&state);
+            state.tick_sync(Action::Move(Direction::Down));
+            assert_eq!(state.player, (2, 0), "{:?}", behavior);
+            assert_eq!(state.lives, 3);
+            // the tick still passes, but the frog, score and combo are untouched
+            let mut idle = level(rows);
+            idle.tick_sync(Action::Idle);
+            assert_eq!(turn_state(&state), turn_state(&idle));
+            assert_ne!(turn_state(&state), before);
+        }
+
+        // the frog falls off, respawning in the middle of the bottom row until its last life
+        let mut state = level(rows);
+        state.config.bottom_edge = EdgeBehavior::Die;
+        state.tick_sync(Action::Move(Direction::Down));
+        assert_eq!(state.lives, 2);
+        assert_eq!(state.player, (7, 0));
+        state.lives = 1;
+        state.tick_sync(Action::Move(Direction::Down));
+        assert!(state.game_over);
+        assert_eq!(state.cause_of_death, Some(CauseOfDeath::FellOffBoard));
+    }
+
+    #[test]
+    fn the_metronome_beats_on_the_ticks_its_row_shifts() {
+        let mut state = level(
+            "grass - - ..............
+             road > 3:1 ##....#.......
+             grass - - ..............
+             player 4 0",
+        );
+        state.config.metronome_row = Some(1);
+        let mut renderer = StringRenderer::default();
+        let mut beats = Vec::new();
+        for tick in 1..=12 {
+            let road = state.gameboard[1].get_base_row().objects.clone();
+            state.tick_sync(Action::Idle);
+            let moved = state.gameboard[1].get_base_row().objects != road;
+            assert_eq!(state.metronome_pulse(), moved, "tick {}", tick);
+            renderer.render_frame(&state).unwrap();
+            assert_eq!(renderer.buffer.contains("🥁"), moved, "tick {}", tick);
+            if moved {
+                beats.push(tick);
+            }
+        }
+        assert_eq!(beats, [2, 5, 8, 11]);
+        // with no metronome row there is never a beat
+        state.config.metronome_row = None;
+        state.tick_sync(Action::Idle);
+        state.tick_sync(Action::Idle);
+        assert!(!state.metronome_pulse());
+    }
+
+    #[test]
+    fn a_center_weighted_curve_puts_more_objects_in_the_middle() {
+        let config = GameConfig {
+            column_density: Some(center_weighted_density),
+            ..offline()
+        };
+        let width = config.board.width;
+        let mut rng = StdRng::seed_from_u64(229);
+        let mut counts = vec![0; width];
+        for _ in 0..2000 {
+            let row = GameState::create_row_of_kind(&config, RowKind::Road, &mut rng);
+            for (column, &car) in row.get_base_row().objects.iter().enumerate() {
+                counts[column] += car as usize;
+            }
+        }
+        let middle = counts[width / 2 - 2..width / 2 + 2].iter().sum::<usize>();
+        let edges = counts[..2]
+            .iter()
+            .chain(&counts[width - 2..])
+            .sum::<usize>();
+        assert!(
+            middle > edges * 2,
+            "middle {} edges {} in {:?}",
+            middle,
+            edges,
+            counts
+        );
+
+        // cell by cell the curve is followed too, not only for whole vehicles
+        let mut counts = vec![0; width];
+        for _ in 0..2000 {
+            let row = BaseRow::randomized_objects_by_column(
+                width,
+                CAR,
+                ROAD,
+                |column| center_weighted_density(column, width),
+                &mut rng,
+            );
+            for (column, &car) in row.objects.iter().enumerate() {
+                counts[column] += car as usize;
+            }
+        }
+        assert!(counts[width / 2] > counts[0] * 4, "{:?}", counts);
+        assert!(counts[width / 2] > counts[width - 1] * 4, "{:?}", counts);
+    }
+
+    #[test]
+    fn the_trace_has_one_line_per_tick() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.log");
+        let mut state = GameState::headless(GameConfig {
+            seed: Some(227),
+            invincible: true,
+            trace_path: Some(path.clone()),
+            ..offline()
+        });
+        let mut expected = Vec::new();
+        for tick in 1..=60u64 {
+            let input = match tick % 4 {
+                0 => None,
+                1 => Some(Direction::Up),
+                2 => Some(Direction::Left),
+                _ => Some(Direction::Right),
+            };
+            state.tick_sync(input.map_or(Action::Idle, Action::Move));
+            expected.push(format!(
+                "{} {},{} {:?} {:016x}",
+                tick,
+                state.player.0,
+                state.player.1,
+                input,
+                state.board_hash()
+            ));
+        }
+        drop(state);
+        let trace = fs::read_to_string(&path).unwrap();
+        assert_eq!(trace.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn a_stopped_slide_can_be_sent_back_the_other_way() {
+        let mut state = level(
+            "grass - - ..............
+             grass - - ..............
+             ice - - ..#......#....
+             grass - - ..............
+             player 5 1",
+        );
+        let slide = |state: &mut GameState, direction: Direction| {
+            state.tick_sync(Action::Move(direction));
+            let mut path = vec![state.player.0];
+            while state.is_sliding() {
+                state.tick_sync(Action::Idle);
+                path.push(state.player.0);
+            }
+            path
+        };
+        // into the tree on the right, then back into the one on the left, then right again
+        assert_eq!(slide(&mut state, Direction::Right), [6, 7, 8, 8]);
+        assert_eq!(slide(&mut state, Direction::Left), [7, 6, 5, 4, 3, 3]);
+        assert_eq!(slide(&mut state, Direction::Right), [4, 5, 6, 7, 8, 8]);
+        // pushing against the wall it stopped at goes nowhere and starts no slide
+        assert_eq!(slide(&mut state, Direction::Right), [8]);
+        assert_eq!(state.stats.trees_blocked_by, 1);
+        assert_eq!(state.player, (8, 1));
+    }
+
+    #[test]
+    fn blocking_cells_turn_the_frog_back_and_lethal_ones_kill_it() {
+        let rows = "grass - - ..............
+             stream < 99:0 ##....@.......
+             road > 99:0 ....#.........
+             grass - - .....#........
+             player 4 0";
+        // a tree and a rock are walls: the move is undone and nothing else happens
+        for (start, direction) in [((4, 0), Direction::Right), ((6, 1), Direction::Up)] {
+            let mut state = level(rows);
+            state.player = start;
+            state.tick_sync(Action::Move(direction));
+            assert_eq!(state.player, start);
+            assert_eq!(state.lives, 3);
+            assert_eq!(state.player_score, 0);
+            assert_eq!(state.stats.trees_blocked_by, 1);
+        }
+        // the car kills on the spot
+        let mut state = level(rows);
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!(state.lives, 2);
+        assert_eq!(state.stats.times_hit_by_car, 1);
+        assert_eq!(state.player, (7, 0));
+        // open water kills once the current has had the frog for its grace
+        let mut state = level(rows);
+        state.player = (8, 1);
+        state.tick_sync(Action::Move(Direction::Up));
+        for _ in 0..WATER_DRIFT_GRACE {
+            assert_eq!(state.lives, 3);
+            state.tick_sync(Action::Idle);
+        }
+        assert_eq!(state.lives, 2);
+        assert_eq!(state.stats.times_drowned, 1);
+    }
+
+    #[test]
+    fn a_tick_hook_reversing_every_few_ticks_swings_the_row() {
+        let mut cars = vec![false; 10];
+        cars[0] = true;
+        let mut row = DynamicRow::new(BaseRow::new(cars, CAR, ROAD), true, 1, 0)
+            .with_tick_hook(TickHook::reverse_every(3));
+        let mut path = Vec::new();
+        for _ in 0..12 {
+            row.tick();
+            path.push(row.row.objects.iter().position(|&car| car).unwrap());
+        }
+        // right for two ticks, then three at a time each way, turning before the shift
+        assert_eq!(path, [1, 2, 1, 0, 9, 0, 1, 2, 1, 0, 9, 0]);
+        assert_eq!(row.shifts(), 12);
+
+        // any closure will do, here one that slows the row down once it has shifted twice
+        let mut row = DynamicRow::new(
+            BaseRow::new(vec![true, false, false, false], CAR, ROAD),
+            true,
+            1,
+            0,
+        )
+        .with_tick_hook(TickHook::new(|row| {
+            if row.shifts() == 2 {
+                row.interval = 4;
+            }
+        }));
+        let shifted: Vec<bool> = (0..10)
+            .map(|_| {
+                row.tick();
+                row.shifted()
+            })
+            .collect();
+        assert_eq!(
+            shifted,
+            [true, true, false, false, false, true, false, false, false, true]
+        );
+    }
+
+    #[tokio::test]
+    async fn the_exported_board_is_the_string_rendered_board() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("board.txt");
+        let mut state = level(
+            "grass - - ..#...........
+             stream < 99:0 ##....##......
+             road > 1:0 ..#...........
+             grass - - ..............
+             player 3 0",
+        );
+        state.theme = Theme::ascii();
+        state.export_board(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), state.render_board());
+
+        // at the end of a run the final board is written out without being asked
+        state.lives = 1;
+        state.config.collision_flash = false;
+        state.config.export_board_path = Some(path.clone());
+        fs::remove_file(&path).unwrap();
+        let script = [Action::Move(Direction::Up), Action::Idle];
+        state.run(Box::new(ScriptedInput::new(script))).await;
+        assert!(state.game_over);
+        let exported = fs::read_to_string(&path).unwrap();
+        assert_eq!(exported, state.render_board());
+        assert_eq!(
+            exported,
+            "..T...........\n\
+             OO~~~~OO~~~~~~\n\
+             ___F__________\n\
+             ..............\n"
+        );
+    }
+
+    #[test]
+    fn the_frog_looks_hurt_on_its_last_life_only_when_asked() {
+        let rows = "grass - - ....
+             grass - - ....
+             player 1 0";
+        let frog_row = |state: &GameState| {
+            state
+                .render_board_with(&Theme::ascii())
+                .lines()
+                .last()
+                .unwrap()
+                .to_string()
+        };
+        let mut state = level(rows);
+        state.config.last_life_glyph = true;
+        assert_eq!(state.lives, 3);
+        assert_eq!(frog_row(&state), ".F..");
+        state.lives = 2;
+        assert_eq!(frog_row(&state), ".F..");
+        state.lives = 1;
+        assert_eq!(frog_row(&state), ".f..");
+        // a theme can choose its own last-life glyph
+        let theme = Theme::ascii().with_last_life_glyph('x').unwrap();
+        assert_eq!(state.render_board_with(&theme).lines().last(), Some(".x.."));
+        // off, one life left looks like any other
+        state.config.last_life_glyph = false;
+        assert_eq!(frog_row(&state), ".F..");
+    }
+
+    #[test]
+    fn the_warmup_eases_the_interval_down_to_the_target() {
+        let target = Duration::from_millis(50);
+        let warmup = Duration::from_secs(2);
+        for (elapsed, interval) in [
+            (0, 100_000),
+            (500, 87_500),
+            (1000, 75_000),
+            (1500, 62_500),
+            (2000, 50_000),
+            (9000, 50_000),
+        ] {
+            assert_eq!(
+                warmup_interval(target, warmup, Duration::from_millis(elapsed)).as_micros(),
+                interval,
+                "after {}ms",
+                elapsed
+            );
+        }
+        // no warmup at all is the target from the start
+        assert_eq!(
+            warmup_interval(target, Duration::ZERO, Duration::ZERO),
+            target
+        );
+
+        // paused frames don't use up the warmup
+        let mut state = open_field();
+        state.config.warmup = Duration::from_secs(1);
+        state.paused = true;
+        for _ in 0..100 {
+            assert_eq!(state.next_frame_interval(), 2 * TICK_INTERVAL);
+        }
+        assert_eq!(state.elapsed, Duration::ZERO);
+        state.paused = false;
+        let intervals: Vec<Duration> = (0..30).map(|_| state.next_frame_interval()).collect();
+        assert_eq!(intervals[0], 2 * TICK_INTERVAL);
+        assert!(intervals.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert_eq!(intervals[29], TICK_INTERVAL);
+        assert_eq!(state.elapsed, intervals.iter().sum());
+    }
+
+    #[test]
+    fn the_last_life_ends_the_game_where_the_frog_died() {
+        let rows = "grass - - ..............
+             stream < 99:0 ..........##..
+             road > 99:0 ....#.........
+             grass - - ..............
+             player 4 0";
+        // with lives to spare the frog respawns on the bottom row
+        let mut state = level(rows);
+        state.tick_sync(Action::Move(Direction::Up));
+        assert_eq!((state.lives, state.game_over), (2, false));
+        assert_eq!(state.player, (7, 0));
+
+        // on its last life it stays where it was hit, and the road is the cause
+        let mut state = level(rows);
+        state.lives = 1;
+        state.tick_sync(Action::Move(Direction::Up));
+        assert!(state.game_over);
+        assert_eq!(state.player, (4, 1));
+        assert_eq!(state.cause_of_death, Some(CauseOfDeath::HitByCar));
+
+        // mid-river it goes under there and then, the current having carried it along
+        let mut state = level(rows);
+        state.lives = 1;
+        state.player = (3, 1);
+        state.tick_sync(Action::Move(Direction::Up));
+        while !state.game_over {
+            state.tick_sync(Action::Idle);
+        }
+        assert_eq!(state.lives, 0);
+        assert_eq!(state.player.1, 2);
+        assert_ne!(state.player.0, 3);
+        assert_eq!(state.cause_of_death, Some(CauseOfDeath::Drowned));
+        assert_eq!(state.stats.times_drowned, 1);
+    }
+
+    #[test]
+    fn replay_diff_finds_where_two_runs_part_ways() {
+        let dir = tempfile::tempdir().unwrap();
+        let play = |name: &str, fourth: Direction| -> Replay {
+            let path = dir.path().join(name);
+            let mut state = open_field();
+            state.config.replay_path = Some(path.clone());
+            state.new_game();
+            for direction in [
+                Direction::Up,
+                Direction::Up,
+                Direction::Up,
+                fourth,
+                Direction::Right,
+            ] {
+                state.tick_sync(Action::Move(direction));
+            }
+            state.replay.as_ref().unwrap().save_to(&path).unwrap();
+            Replay::load_from(&path).unwrap()
+        };
+        let a = play("a.json", Direction::Left);
+        let b = play("b.json", Direction::Up);
+        let diff = diff_replays(&a, &b);
+        assert_eq!(diff.first_divergence, Some(4));
+        // the fourth hop is worth 4 with the combo built up by then
+        assert_eq!(diff.score_deltas, [(1, 0), (2, 0), (3, 0), (4, 4), (5, 4)]);
+
+        // the same run against itself never diverges; cut short, it diverges where it stops
+        assert_eq!(diff_replays(&a, &a).first_divergence, None);
+        let short = Replay {
+            frames: a.frames[..2].to_vec(),
+        };
+        assert_eq!(diff_replays(&a, &short).first_divergence, Some(3));
+        assert_eq!(diff_replays(&short, &a).first_divergence, Some(3));
+    }
+
+    #[test]
+    fn equal_scores_rank_by_the_tie_break() {
+        let entries = [
+            (30, 100, 1),
+            (50, 40, 2),
+            (50, 90, 3),
+            (50, 90, 4),
+            (50, 10, 5),
+        ];
+        let ranked = |tie_break: TieBreak| -> Vec<u64> {
+            let mut scoreboard = Scoreboard::default();
+            // recorded newest first, so the order isn't just the order they came in
+            for &(score, ticks_survived, achieved_at) in entries.iter().rev() {
+                let entry = ScoreEntry {
+                    score,
+                    ticks_survived,
+                    achieved_at,
+                };
+                scoreboard.record(entry, tie_break);
+            }
+            scoreboard
+                .entries
+                .iter()
+                .map(|entry| entry.achieved_at)
+                .collect()
+        };
+        assert_eq!(ranked(TieBreak::EarliestFirst), [2, 3, 4, 5, 1]);
+        // longest survival first, and the earlier of two equal survivals
+        assert_eq!(ranked(TieBreak::LongestSurvival), [3, 4, 2, 5, 1]);
+
+        let mut scoreboard = Scoreboard::default();
+        for policy in [HighScorePolicy::Beat, HighScorePolicy::Match] {
+            assert!(!scoreboard.is_new_high_score(0, policy));
+            assert!(scoreboard.is_new_high_score(1, policy));
+        }
+        scoreboard.record(
+            ScoreEntry {
+                score: 50,
+                ticks_survived: 10,
+                achieved_at: 1,
+            },
+            TieBreak::default(),
+        );
+        assert!(!scoreboard.is_new_high_score(50, HighScorePolicy::Beat));
+        assert!(scoreboard.is_new_high_score(50, HighScorePolicy::Match));
+        for policy in [HighScorePolicy::Beat, HighScorePolicy::Match] {
+            assert!(!scoreboard.is_new_high_score(49, policy));
+            assert!(scoreboard.is_new_high_score(51, policy));
+        }
+    }
+
+    #[test]
+    fn a_narrow_viewport_follows_the_frog_sideways() {
+        let mut state = open_field();
+        state.theme = Theme::ascii();
+        assert_eq!(state.visible_columns(), 0..14);
+        state.config.viewport_width = Some(6);
+        state.config.camera_deadzone = 2;
+        state.update_camera();
+        let mut windows = Vec::new();
+        for direction in [[Direction::Right; 12], [Direction::Left; 12]] {
+            for step in direction {
+                state.tick_sync(Action::Move(step));
+                let columns = state.visible_columns();
+                // away from the board's edges the frog stays in the middle two columns
+                let (column, left) = (state.player.0, columns.start);
+                if left > 0 && columns.end < 14 {
+                    assert!(
+                        (left + 2..=left + 3).contains(&column),
+                        "{:?} {:?}",
+                        column,
+                        columns
+                    );
+                }
+                assert!(columns.contains(&column));
+                let board = state.render_board();
+                assert!(
+                    board.lines().all(|line| line.chars().count() == 6),
+                    "{}",
+                    board
+                );
+                let frog_line = board.lines().last().unwrap();
+                assert_eq!(frog_line.find('F'), Some(column - left));
+                windows.push(left);
+            }
+        }
+        assert_eq!(
+            windows,
+            [2, 3, 4, 5, 6, 7, 8, 8, 8, 8, 8, 8, 8, 8, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0]
+        );
+    }
+
+    #[test]
+    fn disabled_directions_do_nothing_and_the_rest_still_work() {
+        let moved = |direction: Direction, disabled: Option<Direction>| {
+            let mut state = open_field();
+            state.player = (4, 1);
+            match disabled {
+                Some(Direction::Up) => state.config.allow_up = false,
+                Some(Direction::Down) => state.config.allow_down = false,
+                Some(Direction::Left) => state.config.allow_left = false,
+                Some(Direction::Right) => state.config.allow_right = false,
+                None => {}
+            }
+            state.tick_sync(Action::Move(direction));
+            (state.player, state.player_score)
+        };
+        let all = [
+            (Direction::Up, ((4, 2), 1)),
+            (Direction::Down, ((4, 0), 0)),
+            (Direction::Left, ((3, 1), 0)),
+            (Direction::Right, ((5, 1), 0)),
+        ];
+        for (direction, expected) in all {
+            assert_eq!(moved(direction, None), expected, "{:?}", direction);
+        }
+        for (disabled, _) in all {
+            for (direction, expected) in all {
+                let expected = if direction == disabled {
+                    ((4, 1), 0)
+                } else {
+                    expected
+                };
+                assert_eq!(
+                    moved(direction, Some(disabled)),
+                    expected,
+                    "{:?} with {:?} off",
+                    direction,
+                    disabled
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn metrics_add_up_a_scripted_run() {
+        let mut state = level(
+            "grass - - ..............
+             grass - - ..............
+             grass - - ..............
+             road > 99:0 ##............
+             grass - - ..............
+             player 4 0",
+        );
+        let mut coin = vec![CellContent::Empty; 14];
+        coin[4] = CellContent::Coin;
+        state.gameboard[2] = Box::new(Grass::from_contents(coin));
+        for action in [
+            Action::Move(Direction::Up),
+            Action::Move(Direction::Up),
+            Action::Move(Direction::Left),
+            Action::Idle,
+            Action::Move(Direction::Up),
+        ] {
+            state.tick_sync(action);
+        }
+        assert_eq!(
+            state.metrics(),
+            Metrics {
+                // hops worth 1, 2 and 3 with the combo, and a coin
+                score: 1 + 2 + COIN_VALUE + 3,
+                step_count: 5,
+                rows_traversed: 3,
+                lives: 3,
+                game_over: false,
+                cause_of_death: None,
+                seed: 7,
+                // the two cars on the road the frog hopped off
+                cars_dodged: 2,
+                coins: 1,
+                combo: 4,
+                difficulty_level: 0,
+                speed: 1,
+                effects: Vec::new(),
+            }
+        );
+        assert_eq!(state.player, (3, 3));
+    }
+
+    #[test]
+    fn an_impossible_constraint_falls_back_to_open_grass() {
+        let mut rng = StdRng::seed_from_u64(209);
+        let mut spawner = CycleSpawn::default();
+        let row =
+            GameState::generate_row_with_constraints(&mut spawner, &[], |_| false, 5, 14, &mut rng);
+        // every retry was used before giving up
+        assert_eq!(spawner.next, 6);
+        assert_eq!(row.kind(), RowKind::Grass);
+        assert_eq!(row.get_base_row().objects, vec![false; 14]);
+        assert!(GameState::has_open_cell(row.as_ref()));
+        assert!(GameState::is_row_crossable(row.as_ref()));
+
+        // a constraint that can be met stops at the first row that meets it
+        let mut spawner = CycleSpawn::default();
+        let row = GameState::generate_row_with_constraints(
+            &mut spawner,
+            &[],
+            |row| row.kind() == RowKind::Stream,
+            5,
+            14,
+            &mut rng,
+        );
+        assert_eq!(spawner.next, 3);
+        assert_eq!(row.kind(), RowKind::Stream);
+
+        // in a game, a spawner that only offers walls of trees still gets a crossable row
+        struct WallSpawn;
+        impl SpawnStrategy for WallSpawn {
+            fn next_row(
+                &mut self,
+                _history: &[RowKind],
+                _rng: &mut dyn RngCore,
+            ) -> Box<dyn RowType> {
+                Box::new(Grass::new(vec![true; 14]))
+            }
+            fn reset(&mut self, _rng: &mut dyn RngCore) {}
+        }
+        let mut state = open_field().with_spawn_strategy(WallSpawn);
+        state.update_stack();
+        let top = state.gameboard.last().unwrap();
+        assert_eq!(top.get_base_row().objects, vec![false; 14]);
+        assert!(state.is_board_passable());
+    }
+
+    #[test]
+    fn the_collision_frames_show_both_the_frog_and_the_car() {
+        let rows = "grass - - ..............
+             road > 99:0 ....#.........
+             grass - - ..............
+             player 4 0";
+        let road_line = |state: &GameState| -> String {
+            state
+                .render_board_with(&Theme::ascii())
+                .lines()
+                .nth(1)
+                .unwrap()
+                .to_string()
+        };
+        for lives in [1, 3] {
+            let mut state = level(rows);
+            state.config.collision_flash = true;
+            state.lives = lives;
+            state.tick_sync(Action::Move(Direction::Up));
+            assert_eq!(state.lives, lives - 1);
+            assert_eq!(state.collision_at, Some((4, 1)));
+            // the cell flips between the car that hit and the frog it hit, whether the frog
+            // is still lying there or has respawned at the bottom
+            let frog = if lives == 1 { 'F' } else { 'f' };
+            let mut frames = Vec::new();
+            while state.collision_flash > 0 {
+                frames.push(road_line(&state).chars().nth(4).unwrap());
+                state.collision_flash -= 1;
+            }
+            assert_eq!(
+                frames,
+                [frog, 'C', frog, 'C', frog, 'C'],
+                "with {} lives",
+                lives
+            );
+            // once the flash is over the car is drawn as usual, or under the dead frog
+            let after = if lives == 1 { 'F' } else { 'C' };
+            assert_eq!(road_line(&state).chars().nth(4), Some(after));
+        }
+    }
+
+    #[test]
+    fn every_generated_road_has_at_least_the_minimum_of_cars() {
+        let cars = |row: &dyn RowType| row.get_base_row().object_count();
+        let mut rng = StdRng::seed_from_u64(207);
+        for min_cars_per_road in 0..=14 {
+            // a road always keeps one open cell
+            let expected = min_cars_per_road.min(13);
+            let sparse = BoardConfig {
+                object_density: 0.0,
+                ..BoardConfig::default()
+            };
+            for (board, column_density) in [
+                (BoardConfig::default(), None),
+                (sparse.clone(), None),
+                (sparse, Some((|_, _| 0.0) as fn(usize, usize) -> f64)),
+            ] {
+                let config = GameConfig {
+                    min_cars_per_road,
+                    board,
+                    column_density,
+                    ..offline()
+                };
+                for _ in 0..50 {
+                    let row = GameState::create_row_of_kind(&config, RowKind::Road, &mut rng);
+                    assert!(
+                        cars(row.as_ref()) >= expected,
+                        "{} cars for a minimum of {}",
+                        cars(row.as_ref()),
+                        min_cars_per_road
+                    );
+                    assert!(cars(row.as_ref()) < 14);
+                }
+            }
+        }
+        // and so does every road a game spawns as it scrolls
+        let mut state = GameState::headless(GameConfig {
+            seed: Some(207),
+            min_cars_per_road: 3,
+            board: BoardConfig {
+                object_density: 0.0,
+                ..BoardConfig::default()
+            },
+            ..offline()
+        });
+        let mut roads = 0;
+        for _ in 0..300 {
+            state.update_stack();
+            let top = state.gameboard.last().unwrap();
+            if top.kind() == RowKind::Road {
+                roads += 1;
+                assert!(cars(top.as_ref()) >= 3);
+            }
+        }
+        assert!(roads > 50);
+    }
 }